@@ -0,0 +1,53 @@
+use std::env::current_dir;
+use std::fs::create_dir_all;
+
+use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
+
+use cw_starter::hooks::HookExecuteMsg;
+use cw_starter::msg::{
+    AdminsResponse, AllPollsResponse, BallotsResponse, ConfigResponse, CreditsResponse,
+    DelegationChainResponse, EscrowResponse, ExecuteMsg, ExternalRefResponse, HooksResponse,
+    InstantiateMsg, MigrateMsg, NftBallotsResponse, NftVoteResponse, OwnershipResponse,
+    PollResponse, QueryMsg, ReceiveMsg, RoleHoldersResponse, RunoffResponse, TreasuryResponse,
+    VoteResponse, VoterBallotsResponse,
+};
+use cw_starter::state::{Ballot, Config, Poll};
+
+// Writes the JSON schema of every message, response and stored type to schema/
+// Run with `cargo run --example schema` after changing any of them
+fn main() {
+    let mut out_dir = current_dir().unwrap();
+    out_dir.push("schema");
+    create_dir_all(&out_dir).unwrap();
+    remove_schemas(&out_dir).unwrap();
+
+    export_schema(&schema_for!(InstantiateMsg), &out_dir);
+    export_schema(&schema_for!(ExecuteMsg), &out_dir);
+    export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
+    export_schema(&schema_for!(ReceiveMsg), &out_dir);
+    export_schema(&schema_for!(HookExecuteMsg), &out_dir);
+
+    export_schema(&schema_for!(Poll), &out_dir);
+    export_schema(&schema_for!(Ballot), &out_dir);
+    export_schema(&schema_for!(Config), &out_dir);
+
+    export_schema(&schema_for!(AllPollsResponse), &out_dir);
+    export_schema(&schema_for!(PollResponse), &out_dir);
+    export_schema(&schema_for!(ExternalRefResponse), &out_dir);
+    export_schema(&schema_for!(VoteResponse), &out_dir);
+    export_schema(&schema_for!(NftVoteResponse), &out_dir);
+    export_schema(&schema_for!(BallotsResponse), &out_dir);
+    export_schema(&schema_for!(NftBallotsResponse), &out_dir);
+    export_schema(&schema_for!(VoterBallotsResponse), &out_dir);
+    export_schema(&schema_for!(RunoffResponse), &out_dir);
+    export_schema(&schema_for!(CreditsResponse), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(TreasuryResponse), &out_dir);
+    export_schema(&schema_for!(HooksResponse), &out_dir);
+    export_schema(&schema_for!(EscrowResponse), &out_dir);
+    export_schema(&schema_for!(RoleHoldersResponse), &out_dir);
+    export_schema(&schema_for!(AdminsResponse), &out_dir);
+    export_schema(&schema_for!(OwnershipResponse), &out_dir);
+    export_schema(&schema_for!(DelegationChainResponse), &out_dir);
+}
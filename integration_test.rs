@@ -2,9 +2,14 @@
 #![allow(unused_variables)]
 #![allow(unused_imports)]
 
+use anyhow::{bail, Result as AnyResult};
 use cw_multi_test::{
-    App, BankKeeper, Contract, ContractWrapper, Executor, AppResponse
+    App, AppBuilder, BankKeeper, Contract, ContractWrapper, CosmosRouter, Executor, AppResponse,
+    Module, Staking, StakingSudo,
 };
+use cw_storage_plus::Map;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 
 use cosmwasm_std::testing::
     {mock_env, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
@@ -14,16 +19,107 @@ use cosmwasm_std::testing::
     };
 
 use cosmwasm_std::{Validator, QueryResponse, QuerierResult} ;
+use cosmwasm_std::{
+    AllDelegationsResponse, Api, Binary, BlockInfo, BondedDenomResponse, CosmosMsg, CustomQuery,
+    Delegation, Order, Querier, StdResult,
+};
 use cw_starter::{ContractError, contract};
 
 use crate::contract::{execute, instantiate, query}; // the contract instantiate function
 use cw_starter::msg::{
     AllPollsResponse, ExecuteMsg, InstantiateMsg, PollResponse, QueryMsg, VoteResponse,
 };
+use cw_starter::state::PollType;
 use cosmwasm_std::testing::{mock_dependencies, mock_info}; // mock functions to mock an environment, message info, dependencies// helper to construct an attribute e.g. ("action", "instantiate") // our instantiate method
 
 
 
+// cw-multi-test only ships a staking module that fails every call.
+// This one records delegations so the contract can query a voter's stake,
+// the delegated tokens are not moved out of the delegator's bank balance.
+struct StakeKeeper {}
+
+const DELEGATIONS: Map<(&Addr, &str), Uint128> = Map::new("delegations");
+
+impl Staking for StakeKeeper {}
+
+impl Module for StakeKeeper {
+    type ExecT = StakingMsg;
+    type QueryT = StakingQuery;
+    type SudoT = StakingSudo;
+
+    fn execute<ExecC, QueryC>(
+        &self,
+        _api: &dyn Api,
+        storage: &mut dyn Storage,
+        _router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+        _block: &BlockInfo,
+        sender: Addr,
+        msg: StakingMsg,
+    ) -> AnyResult<AppResponse>
+    where
+        ExecC: std::fmt::Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+        QueryC: CustomQuery + DeserializeOwned + 'static,
+    {
+        match msg {
+            StakingMsg::Delegate { validator, amount } => {
+                DELEGATIONS.update(storage, (&sender, &validator), |stake| -> StdResult<_> {
+                    Ok(stake.unwrap_or_default() + amount.amount)
+                })?;
+                Ok(AppResponse::default())
+            }
+            other => bail!("Unsupported staking message: {:?}", other),
+        }
+    }
+
+    fn sudo<ExecC, QueryC>(
+        &self,
+        _api: &dyn Api,
+        _storage: &mut dyn Storage,
+        _router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+        _block: &BlockInfo,
+        msg: StakingSudo,
+    ) -> AnyResult<AppResponse>
+    where
+        ExecC: std::fmt::Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+        QueryC: CustomQuery + DeserializeOwned + 'static,
+    {
+        bail!("Unsupported staking sudo: {:?}", msg)
+    }
+
+    fn query(
+        &self,
+        _api: &dyn Api,
+        storage: &dyn Storage,
+        _querier: &dyn Querier,
+        _block: &BlockInfo,
+        request: StakingQuery,
+    ) -> AnyResult<Binary> {
+        match request {
+            StakingQuery::BondedDenom {} => Ok(to_binary(&BondedDenomResponse {
+                denom: "ukuji".to_string(),
+            })?),
+            StakingQuery::AllDelegations { delegator } => {
+                let delegator = Addr::unchecked(delegator);
+                let delegations = DELEGATIONS
+                    .prefix(&delegator)
+                    .range(storage, None, None, Order::Ascending)
+                    .map(|item| {
+                        let (validator, amount) = item?;
+                        Ok(Delegation {
+                            delegator: delegator.clone(),
+                            validator,
+                            amount: Coin { denom: "ukuji".to_string(), amount },
+                        })
+                    })
+                    .collect::<StdResult<Vec<_>>>()?;
+                Ok(to_binary(&AllDelegationsResponse { delegations })?)
+            }
+            other => bail!("Unsupported staking query: {:?}", other),
+        }
+    }
+}
+
 fn mock_app(owner: &Addr, coins: Vec<Coin>) -> App {
     App::new(|
         router, 
@@ -33,7 +129,7 @@ fn mock_app(owner: &Addr, coins: Vec<Coin>) -> App {
             // initialization moved to App construction. Closure is defined by this block
             router
             .bank
-            .init_balance(storage, &owner, coins)
+            .init_balance(storage, owner, coins)
             .unwrap()
         }
     )
@@ -117,7 +213,13 @@ fn try_mock_app(){
 
     let contract_id = store_token_code(&mut router);
     
-    let init_msg = InstantiateMsg { admin: None };
+    let init_msg = InstantiateMsg {
+        admin: None,
+        validators: None,
+        fees: None,
+        deposit: None,
+        group_addr: None,
+    };
 
     let contract_address = router.instantiate_contract(
         contract_id, owner.clone(), 
@@ -137,13 +239,24 @@ fn try_mock_app(){
         }]).unwrap();
 
     let create_poll_msg_1 = ExecuteMsg::CreatePoll {
-        poll_id: "some_id_1".to_string(),
+        external_ref: None,
         question: "What's your favourite type of fish?".to_string(),
         options: vec![
             "Orca".to_string(),
             "Beluga".to_string(),
             "Remora".to_string(),
         ],
+        poll_type: PollType::Plurality,
+        category: None,
+        start: None,
+        end: None,
+        reveal_end: None,
+        quorum: None,
+        threshold: None,
+        token: None,
+        nft: None,
+        merkle_root: None,
+        msgs: vec![],
     };
 
     let create_poll_res_1 = router.execute_contract(
@@ -155,7 +268,7 @@ fn try_mock_app(){
         }]).unwrap();
 
     let create_poll_msg_2 = ExecuteMsg::CreatePoll {
-        poll_id: "some_id_2".to_string(),
+        external_ref: None,
         question: "What's your vote for proposal 15?".to_string(),
         options: vec![
             "Yes".to_string(),
@@ -163,6 +276,17 @@ fn try_mock_app(){
             "Abstain".to_string(),
             "No_With_Veto".to_string(),
         ],
+        poll_type: PollType::Plurality,
+        category: None,
+        start: None,
+        end: None,
+        reveal_end: None,
+        quorum: None,
+        threshold: None,
+        token: None,
+        nft: None,
+        merkle_root: None,
+        msgs: vec![],
     };
 
     let create_poll_res_2 = router.execute_contract(
@@ -173,7 +297,7 @@ fn try_mock_app(){
             amount: Uint128::new(2u128),
         }]).unwrap();
 
-    let execute_vote_msg = ExecuteMsg::Vote{poll_id: "some_id_2".into(), vote: "Yes".into()};
+    let execute_vote_msg = ExecuteMsg::Vote{poll_id: 2, vote: vec!["Yes".into()], proof: None};
 
     let execute_vote_res = router.execute_contract(
         owner.clone(), contract_address.clone(), &execute_vote_msg, 
@@ -184,7 +308,7 @@ fn try_mock_app(){
         }]
     );
 
-    // let close_poll_msg = ExecuteMsg::ClosePoll { poll_id: ("some_id_2".into()) };
+    // let close_poll_msg = ExecuteMsg::ClosePoll { poll_id: 2 };
 
     // let execute_close_poll_res = router.execute_contract(
     //     owner.clone(), contract_address.clone(), 
@@ -193,7 +317,7 @@ fn try_mock_app(){
     //         amount: Uint128::new(3u128),
     //     }]).unwrap();
     
-    let second_vote_msg = ExecuteMsg::Vote { poll_id: "some_id_2".into(), vote: "Yes".into() };
+    let second_vote_msg = ExecuteMsg::Vote { poll_id: 2, vote: vec!["Yes".into()], proof: None };
 
     let second_vote_res = router.execute_contract(
         lfg.clone(), contract_address.clone(), &second_vote_msg, 
@@ -206,19 +330,26 @@ fn try_mock_app(){
 
 
 
-    let query_all_polls_msg = QueryMsg::AllPolls {};
+    let query_all_polls_msg = QueryMsg::AllPolls {
+        start_after: None,
+        limit: None,
+        closed: None,
+        creator: None,
+        created_after: None,
+        created_before: None,
+    };
     
     let query_all_polls_res: AllPollsResponse = router
         .wrap()
         .query_wasm_smart(contract_address.clone(), &query_all_polls_msg).unwrap();
     
 
-    let query_poll_msg = QueryMsg::Poll { poll_id: "some_id_2".to_string() };
+    let query_poll_msg = QueryMsg::Poll { poll_id: 2 };
 
     let query_poll_res: PollResponse = router
         .wrap().query_wasm_smart(contract_address.clone(), &query_poll_msg).unwrap();
 
-    let query_vote_msg = QueryMsg::Vote { poll_id:"some_id_2".to_string(), address: lfg.to_string() };
+    let query_vote_msg = QueryMsg::Vote { poll_id: 2, address: lfg.to_string() };
 
     let query_vote_res: VoteResponse = router
         .wrap().query_wasm_smart(contract_address.clone(), &query_vote_msg).unwrap();
@@ -228,17 +359,126 @@ fn try_mock_app(){
 
     
     println!("query_all_polls_res: {query_all_polls_res:?}");
-    println!("");
+    println!();
     // println!("execute_close_poll_res: {:?}", execute_close_poll_res);
-    println!("");
+    println!();
     println!("second_vote_res: {:?}", second_vote_res);
-    println!("");
+    println!();
     println!("query_poll_res: {query_poll_res:?}");
-    println!("");
+    println!();
     println!("add_admin_res: {:?}", add_admin_res);
-    println!("");
+    println!();
     println!("contract data: {:?}", contract_data);
-    println!("");
+    println!();
     println!("query_vote_res: {:?}", query_vote_res);
 
-}
\ No newline at end of file
+}
+
+#[test]
+fn stake_weighted_vote() {
+    let owner = Addr::unchecked("owner");
+    let alice = Addr::unchecked("alice");
+    let lfg = Addr::unchecked("lfg");
+    let validator = "kujiravaloper1546l88y0g9ch5v25dg4lmfewgelsd3v966qj3y";
+    let other_validator = "kujiravaloper1other";
+
+    let mut router = AppBuilder::new()
+        .with_staking(StakeKeeper {})
+        .build(|router, _, storage| {
+            for address in [&owner, &alice, &lfg] {
+                router
+                    .bank
+                    .init_balance(storage, address, vec![Coin::new(1_000, "ukuji")])
+                    .unwrap();
+            }
+        });
+
+    // Alice delegates 600 to the kujiDAO validator,
+    // lfg delegates 50 to it and 100 to a validator outside the configured set
+    for (delegator, validator, amount) in [
+        (&alice, validator, 600u128),
+        (&lfg, validator, 50),
+        (&lfg, other_validator, 100),
+    ] {
+        router
+            .execute(
+                delegator.clone(),
+                CosmosMsg::Staking(StakingMsg::Delegate {
+                    validator: validator.to_string(),
+                    amount: Coin::new(amount, "ukuji"),
+                }),
+            )
+            .unwrap();
+    }
+
+    let contract_id = router.store_code(Box::new(ContractWrapper::new_with_empty(
+        contract::execute,
+        contract::instantiate,
+        contract::query,
+    )));
+    let init_msg = InstantiateMsg {
+        admin: None,
+        validators: Some(vec![validator.to_string()]),
+        fees: None,
+        deposit: None,
+        group_addr: None,
+    };
+    let contract_address = router
+        .instantiate_contract(contract_id, owner.clone(), &init_msg, &[], "CONTRACT", None)
+        .unwrap();
+
+    let create_poll_msg = ExecuteMsg::CreatePoll {
+        external_ref: None,
+        question: "What's your vote for proposal 15?".to_string(),
+        options: vec!["Yes".to_string(), "No".to_string()],
+        poll_type: PollType::Plurality,
+        category: None,
+        start: None,
+        end: None,
+        reveal_end: None,
+        quorum: None,
+        threshold: None,
+        token: None,
+        nft: None,
+        merkle_root: None,
+        msgs: vec![],
+    };
+    let fee = [Coin::new(1, "ukuji")];
+    router
+        .execute_contract(owner.clone(), contract_address.clone(), &create_poll_msg, &fee)
+        .unwrap();
+
+    for (voter, vote) in [(&alice, "Yes"), (&lfg, "No")] {
+        let vote_msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec![vote.to_string()],
+            proof: None,
+        };
+        router
+            .execute_contract(voter.clone(), contract_address.clone(), &vote_msg, &fee)
+            .unwrap();
+    }
+
+    // The owner has nothing delegated and cannot vote
+    let vote_msg = ExecuteMsg::Vote {
+        poll_id: 1,
+        vote: vec!["No".to_string()],
+        proof: None,
+    };
+    let err = router
+        .execute_contract(owner.clone(), contract_address.clone(), &vote_msg, &fee)
+        .unwrap_err();
+    assert!(matches!(err.downcast_ref::<ContractError>(), Some(ContractError::NotStaked {})));
+
+    let query_poll_res: PollResponse = router
+        .wrap()
+        .query_wasm_smart(contract_address, &QueryMsg::Poll { poll_id: 1 })
+        .unwrap();
+    assert_eq!(
+        query_poll_res.poll.unwrap().options,
+        vec![
+            ("Yes".to_string(), Uint128::new(600)),
+            ("No".to_string(), Uint128::new(50)),
+        ]
+    );
+}
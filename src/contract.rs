@@ -1,22 +1,50 @@
 
 
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, 
-    Env, MessageInfo, Order, Response, StdResult, 
-    Uint128, entry_point, Addr
+    from_binary, to_binary, BankMsg, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut,
+    Env, MessageInfo, Order, Response, StakingMsg, StdError, StdResult, Storage, Timestamp,
+    Uint128, WasmMsg, entry_point, Addr
 };
-use cw2::set_contract_version;
+use cw_storage_plus::Bound;
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw_utils::{must_pay, nonpayable, Expiration};
+use semver::Version;
+use std::cmp::Ordering;
 
 use crate::error::ContractError;
+use crate::events;
+use crate::helpers::{
+    commitment, instant_runoff, poll_result, quadratic_cost, verify_allowlist,
+};
+use crate::hooks::{prepare_hooks, HookExecuteMsg, PollHookMsg, VoteHookMsg};
+use crate::migrations::MIGRATIONS;
 use crate::msg::{
-    AllPollsResponse, ExecuteMsg, InstantiateMsg, PollResponse, QueryMsg, VoteResponse,
+    AdminInfo, AdminsResponse, AllowlistProof, AllPollsResponse, BallotsResponse, ConfigResponse, CreditsResponse,
+    Cw4QueryMsg, Cw721QueryMsg, DelegationChainResponse, EscrowResponse, ExecuteMsg,
+    ExternalRefResponse, HooksResponse, InstantiateMsg, MemberResponse, MigrateMsg,
+    NftBallotsResponse, NftVoteResponse, OwnerOfResponse, OwnershipResponse, PollInfo,
+    PollResponse, QueryMsg, ReceiveMsg, RoleHoldersResponse, RunoffResponse, TotalWeightResponse, TreasuryResponse,
+    VoteResponse, VoterBallot, VoterBallotsResponse,
 };
 
-use crate::state::{Ballot, Config, Poll, BALLOTS, CONFIG, POLLS};
+use crate::state::{
+    ballots, delegations, polls, Ballot, Commit, Config, Delegation, DelegationScope,
+    DepositConfig, Fees, GroupSnapshot, NftBallot, Outcome, PendingOwner, Poll, PollResult,
+    PollStatus, PollType, ProxyVote, Role, Threshold, COMMITS, CONFIG, ESCROWS, ESCROW_TOKENS,
+    EXTERNAL_REFS, HOOKS, NFT_BALLOTS, PENDING_OWNER, POLL_COUNT, PROXY_VOTES, ROLES, RUNOFFS,
+    TREASURY,
+};
 
 const CONTRACT_NAME: &str = "crates.io:cw-starter";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// Page sizes for listing polls and ballots
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+// Most polls a filtered page looks at, so filters that match little can't scan every poll
+const MAX_SCAN: usize = 100;
+
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -28,10 +56,21 @@ pub fn instantiate(
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     let admin = msg.admin.unwrap_or_else(|| info.sender.to_string());
     let validated_admin = deps.api.addr_validate(&admin)?;
+    let group_addr = msg
+        .group_addr
+        .map(|group_addr| deps.api.addr_validate(&group_addr))
+        .transpose()?;
+    if let Some(deposit) = &msg.deposit {
+        validate_deposit(deposit)?;
+    }
     let config = Config {
-        admins: vec![validated_admin.clone()],
+        validators: msg.validators,
+        fees: msg.fees.unwrap_or_default(),
+        deposit: msg.deposit,
+        group_addr,
     };
     CONFIG.save(deps.storage, &config)?;
+    ROLES.save(deps.storage, (&validated_admin, Role::Owner), &())?;
     Ok(Response::new()
         .add_attribute("action", "instantiate")
         .add_attribute("admin", validated_admin.to_string()))
@@ -44,621 +83,4811 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
-    if info.funds[0].denom != "ukuji" || info.funds[0].amount < Uint128::from(1u32) {
-        return Err(ContractError::BadDenom{});
+    let config = CONFIG.load(deps.storage)?;
+    let fees = config.fees;
+    let fee = match &msg {
+        ExecuteMsg::CreatePoll { .. } => fees.create_poll,
+        ExecuteMsg::Vote { .. }
+        | ExecuteMsg::NftVote { .. }
+        | ExecuteMsg::Commit { .. }
+        | ExecuteMsg::Delegate { .. }
+        | ExecuteMsg::Undelegate { .. } => fees.vote,
+        // Revealing is free so nobody is put off counting their ballot
+        // Token votes come from the token contract, which can't pay fees
+        ExecuteMsg::Reveal { .. }
+        | ExecuteMsg::Receive(_)
+        | ExecuteMsg::ClaimTokens { .. } => Uint128::zero(),
+        ExecuteMsg::ClosePoll { .. } | ExecuteMsg::Execute { .. } => fees.close_poll,
+        ExecuteMsg::AddAdmin { .. }
+        | ExecuteMsg::RemoveAdmin { .. }
+        | ExecuteMsg::GrantRole { .. }
+        | ExecuteMsg::RevokeRole { .. }
+        | ExecuteMsg::ProposeOwner { .. }
+        | ExecuteMsg::AcceptOwnership {}
+        | ExecuteMsg::RenounceOwnership {}
+        | ExecuteMsg::UpdateConfig { .. }
+        | ExecuteMsg::WithdrawFees { .. }
+        | ExecuteMsg::AddHook { .. }
+        | ExecuteMsg::RemoveHook { .. } => fees.admin,
+    };
+    // Poll creators also lock the deposit
+    let deposit = match (&msg, config.deposit) {
+        (ExecuteMsg::CreatePoll { .. }, Some(deposit)) => deposit.amount,
+        _ => Uint128::zero(),
+    };
+    // Free actions take no payment, funds sent with them would be stuck outside the treasury
+    let required = fee + deposit;
+    if required.is_zero() {
+        nonpayable(&info)?;
+    } else {
+        let paid = must_pay(&info, &fees.denom)?;
+        if paid < required {
+            return Err(ContractError::InsufficientFee { fee: required, denom: fees.denom });
+        }
+        // Everything paid apart from the deposit goes to the treasury
+        TREASURY.update(deps.storage, &fees.denom, |balance| -> StdResult<_> {
+            Ok(balance.unwrap_or_default() + paid - deposit)
+        })?;
     }
     match msg {
         ExecuteMsg::CreatePoll {
-            poll_id,
+            external_ref,
             question,
             options,
-        } => execute_create_poll(deps, env, info, poll_id, question, options),
-        ExecuteMsg::Vote { poll_id, vote } => execute_vote(deps, env, info, poll_id, vote),
+            poll_type,
+            category,
+            start,
+            end,
+            reveal_end,
+            quorum,
+            threshold,
+            token,
+            nft,
+            merkle_root,
+            msgs,
+        } => execute_create_poll(
+            deps, env, info, external_ref, question, options, poll_type, category, start, end,
+            reveal_end, quorum, threshold, token, nft, merkle_root, msgs,
+        ),
+        ExecuteMsg::NftVote { poll_id, token_ids, vote } => {
+            execute_nft_vote(deps, env, info, poll_id, token_ids, vote)
+        }
+        ExecuteMsg::Vote { poll_id, vote, proof } => {
+            execute_vote(deps, env, info, poll_id, vote, proof)
+        }
+        ExecuteMsg::Delegate { to, scope } => execute_delegate(deps, env, info, to, scope),
+        ExecuteMsg::Undelegate { scope } => execute_undelegate(deps, env, info, scope),
+        ExecuteMsg::Commit { poll_id, commitment } => {
+            execute_commit(deps, env, info, poll_id, commitment)
+        }
+        ExecuteMsg::Reveal { poll_id, vote, salt } => {
+            execute_reveal(deps, env, info, poll_id, vote, salt)
+        }
+        ExecuteMsg::Receive(wrapper) => execute_receive(deps, env, info, wrapper),
+        ExecuteMsg::ClaimTokens { poll_id } => claim_tokens(deps, env, info, poll_id),
         ExecuteMsg::ClosePoll {poll_id} => close_poll(deps, env, info, poll_id),
+        ExecuteMsg::Execute { poll_id } => execute_poll_msgs(deps, env, info, poll_id),
         ExecuteMsg::AddAdmin { addmin } => add_admin(deps, env, info, addmin),
+        ExecuteMsg::RemoveAdmin { address } => remove_admin(deps, env, info, address),
+        ExecuteMsg::GrantRole { address, role } => grant_role(deps, env, info, address, role),
+        ExecuteMsg::RevokeRole { address, role } => revoke_role(deps, env, info, address, role),
+        ExecuteMsg::ProposeOwner { new_owner, expiry } => {
+            propose_owner(deps, env, info, new_owner, expiry)
+        }
+        ExecuteMsg::AcceptOwnership {} => accept_ownership(deps, env, info),
+        ExecuteMsg::RenounceOwnership {} => renounce_ownership(deps, env, info),
+        ExecuteMsg::UpdateConfig { fees, deposit } => update_config(deps, env, info, fees, deposit),
+        ExecuteMsg::WithdrawFees { amount, recipient } => {
+            withdraw_fees(deps, env, info, amount, recipient)
+        }
+        ExecuteMsg::AddHook { addr } => add_hook(deps, env, info, addr),
+        ExecuteMsg::RemoveHook { addr } => remove_hook(deps, env, info, addr),
     }
 }
 
 // Function to create poll, called in execute function
+#[allow(clippy::too_many_arguments)]
 fn execute_create_poll(
     deps: DepsMut,
-    _env: Env, // _env as we won't be using it
+    env: Env,
     info: MessageInfo,
-    poll_id: String,
+    external_ref: Option<String>,
     question: String,
     options: Vec<String>,
+    poll_type: PollType,
+    category: Option<String>,
+    start: Option<Expiration>,
+    end: Option<Expiration>,
+    reveal_end: Option<Expiration>,
+    mut quorum: Option<Threshold>,
+    mut threshold: Option<Threshold>,
+    token: Option<String>,
+    nft: Option<String>,
+    merkle_root: Option<Binary>,
+    msgs: Vec<CosmosMsg>,
 ) -> Result<Response, ContractError> {
-    if !CONFIG.load(deps.storage)?.admins.contains(&info.sender) {
-        return Err(ContractError::Unauthorized {})
-    };
+    // Anyone can create a poll when deposits are open, otherwise only poll creators
+    // Messages can spend the contract's funds, so attaching them always takes the role
+    let config = CONFIG.load(deps.storage)?;
+    let deposit = config.deposit.clone().filter(|deposit| !deposit.amount.is_zero());
+    let open_creation = deposit.as_ref().map_or(false, |deposit| deposit.open_creation);
+    if !open_creation || !msgs.is_empty() {
+        ensure_role(deps.storage, &info.sender, Role::PollCreator)?;
+    }
     if options.len() > 10 {
         return Err(ContractError::TooManyOptions{});
     }
-    let mut opts: Vec<(String, u64)> = vec![];
+    // Leaving the veto option out would keep the deposit safe from being slashed
+    let veto = deposit.as_ref().and_then(|deposit| deposit.veto.clone());
+    if let Some(veto) = &veto {
+        if !options.contains(&veto.option) {
+            return Err(ContractError::MissingVetoOption { option: veto.option.clone() });
+        }
+    }
+    if let PollType::Approval { max_selections } = poll_type {
+        if max_selections == 0 {
+            return Err(ContractError::NoSelections {});
+        }
+    }
+    // A poll that can never open, has already ended or ends before it opens is rejected
+    if start == Some(Expiration::Never {}) || end.map_or(false, |end| end.is_expired(&env.block)) {
+        return Err(ContractError::InvalidPeriod {});
+    }
+    if let (Some(start), Some(end)) = (start, end) {
+        if end.partial_cmp(&start) != Some(Ordering::Greater) {
+            return Err(ContractError::InvalidPeriod {});
+        }
+    }
+    // Secret ballot polls need a voting period and a reveal phase after it
+    if let Some(reveal_end) = reveal_end {
+        match end {
+            Some(end) if reveal_end.partial_cmp(&end) == Some(Ordering::Greater) => {}
+            _ => return Err(ContractError::InvalidPeriod {}),
+        }
+    }
+    for rule in quorum.iter().chain(threshold.iter()) {
+        let percentage = match rule {
+            Threshold::AbsoluteCount { .. } => continue,
+            Threshold::VotesCast { percentage } => percentage,
+            Threshold::EligiblePower { percentage, .. } => percentage,
+        };
+        if percentage.is_zero() || *percentage > Decimal::one() {
+            return Err(ContractError::InvalidThreshold {});
+        }
+    }
+    if !msgs.is_empty() && (poll_type != PollType::Plurality || options.len() != 2) {
+        return Err(ContractError::NotExecutable {});
+    }
+    // Otherwise a single yes vote would be enough to run them
+    if !msgs.is_empty() && (quorum.is_none() || threshold.is_none()) {
+        return Err(ContractError::NeedsThreshold {});
+    }
+    let token = token.map(|token| deps.api.addr_validate(&token)).transpose()?;
+    if let Some(token) = &token {
+        ESCROW_TOKENS.save(deps.storage, token, &())?;
+    }
+    check_escrowed_tokens(deps.storage, &msgs)?;
+    if token.is_some() && reveal_end.is_some() {
+        return Err(ContractError::TokenSecretBallot {});
+    }
+    let nft = nft.map(|nft| deps.api.addr_validate(&nft)).transpose()?;
+    if nft.is_some() && (token.is_some() || reveal_end.is_some()) {
+        return Err(ContractError::InvalidNftPoll {});
+    }
+    if merkle_root.is_some() && (token.is_some() || nft.is_some() || reveal_end.is_some()) {
+        return Err(ContractError::InvalidAllowlistPoll {});
+    }
+    // Token weighted, NFT gated and allowlisted polls don't need the group
+    let group = config
+        .group_addr
+        .clone()
+        .filter(|_| token.is_none() && nft.is_none() && merkle_root.is_none())
+        .map(|addr| GroupSnapshot { addr, height: env.block.height });
+    // The group knows its eligible power, so the creator's figure isn't trusted
+    if let Some(group) = &group {
+        for rule in quorum.iter_mut().chain(threshold.iter_mut()) {
+            if let Threshold::EligiblePower { eligible, .. } = rule {
+                let query = Cw4QueryMsg::TotalWeight { at_height: Some(group.height) };
+                let total: TotalWeightResponse =
+                    deps.querier.query_wasm_smart(&group.addr, &query)?;
+                *eligible = Uint128::from(total.weight);
+            }
+        }
+    }
+    // A quorum every poll reaches can't tell an ignored poll from one people voted on
+    let always_reached = match &quorum {
+        Some(Threshold::VotesCast { .. }) => true,
+        Some(Threshold::AbsoluteCount { weight }) => weight.is_zero(),
+        Some(Threshold::EligiblePower { eligible, .. }) => eligible.is_zero(),
+        None => false,
+    };
+    if always_reached {
+        return Err(ContractError::InvalidQuorum {});
+    }
+    // Without one a poll nobody votes on would get its deposit back
+    if deposit.is_some() && quorum.is_none() {
+        return Err(ContractError::NeedsQuorum {});
+    }
+    let mut opts: Vec<(String, Uint128)> = vec![];
     for option in options {
-        opts.push((option, 0));
+        opts.push((option, Uint128::zero()));
+    }
+
+    // References have to be unique, otherwise polls could be looked up under the wrong id
+    if let Some(external_ref) = &external_ref {
+        if EXTERNAL_REFS.has(deps.storage, external_ref) {
+            return Err(ContractError::DuplicateExternalRef { external_ref: external_ref.clone() });
+        }
+    }
+    let poll_id = POLL_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    POLL_COUNT.save(deps.storage, &poll_id)?;
+    if let Some(external_ref) = &external_ref {
+        EXTERNAL_REFS.save(deps.storage, external_ref, &poll_id)?;
     }
 
     let poll = Poll {
         creator: info.sender,
+        external_ref,
+        created: env.block.time,
         question,
-        options: opts,
+        options: opts.clone(),
         closed: false,
+        poll_type,
+        category,
+        start,
+        end,
+        reveal_end,
+        unrevealed: Uint128::zero(),
+        turnout: Uint128::zero(),
+        quorum,
+        threshold,
+        result: None,
+        msgs,
+        executed: false,
+        deposit: deposit.as_ref().map(|deposit| Coin {
+            denom: config.fees.denom.clone(),
+            amount: deposit.amount,
+        }),
+        burn: deposit.as_ref().map_or(false, |deposit| deposit.burn),
+        veto,
+        group,
+        token,
+        nft,
+        merkle_root,
     };
 
-    POLLS.save(deps.storage, &poll_id, &poll)?;
+    polls().save(deps.storage, poll_id, &poll)?;
 
-    Ok(Response::new().add_attribute("action", "create poll"))
+    let hook = PollHookMsg::Created { poll_id, creator: poll.creator.to_string() };
+    Ok(Response::new()
+        .add_submessages(prepare_hooks(deps.storage, HookExecuteMsg::PollHook(hook))?)
+        .add_event(events::poll_created(poll_id, &poll)?)
+        .add_attribute("action", "create poll")
+        .add_attribute("poll_id", poll_id.to_string())
+        .set_data(to_binary(&poll_id)?))
 }
 
 // Function to execute a vote message called in Execute function
 fn execute_vote(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    poll_id: String,
-    vote: String,
+    poll_id: u64,
+    vote: Vec<String>,
+    proof: Option<AllowlistProof>,
 ) -> Result<Response, ContractError> {
-
-    let poll = POLLS.may_load(deps.storage, &poll_id)?;
-    println!("{}",poll.clone().unwrap().closed);
-    if poll.clone().unwrap().closed == true {
-        return Err(ContractError::ClosedPoll {})
+    let mut poll = polls()
+        .may_load(deps.storage, poll_id)?
+        .ok_or(ContractError::NoPoll {})?;
+    if poll.reveal_end.is_some() {
+        return Err(ContractError::CommitRevealPoll {});
+    }
+    if poll.token.is_some() {
+        return Err(ContractError::TokenPoll {});
     }
+    if poll.nft.is_some() {
+        return Err(ContractError::NftPoll {});
+    }
+    ensure_open(&poll, &env)?;
 
-    // if !deps.querier
-    // .query_all_delegations(&info.sender)?
-    // .into_iter()
-    // .any(|delegation|delegation.validator=="kujiravaloper1546l88y0g9ch5v25dg4lmfewgelsd3v966qj3y".to_string()) {
-    //     return Err(ContractError::NotStaked{})
-    // }
+    // The ballot carries the power of everyone who delegated to the voter and hasn't voted
+    // Allowlisted polls only count the voter's own allowance
+    let config = CONFIG.load(deps.storage)?;
+    let mut represented: Vec<(Addr, Uint128)> = vec![];
+    if let Some(root) = &poll.merkle_root {
+        let proof = proof.ok_or(ContractError::NotAllowlisted {})?;
+        if !verify_allowlist(root, &info.sender, proof.weight, &proof.proof) {
+            return Err(ContractError::NotAllowlisted {});
+        }
+        represented.push((info.sender.clone(), proof.weight.unwrap_or(Uint128::new(1))));
+    } else {
+        for member in represented_voters(deps.storage, &poll, poll_id, &info.sender)? {
+            let power = voting_power(deps.as_ref(), &config, &poll, &member)?;
+            represented.push((member, power));
+        }
+    }
+    let weight: Uint128 = represented.iter().map(|member| member.1).sum();
+    if weight.is_zero() {
+        return Err(no_power(&poll));
+    }
+    validate_ballot(&poll, &vote)?;
 
+    // A revote starts from scratch, so forget the power the old ballot carried
+    let proxied = PROXY_VOTES
+        .prefix(poll_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .filter_map(|item| match item {
+            Ok((delegator, proxy)) if proxy.voter == info.sender => Some(Ok(delegator)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    for delegator in proxied {
+        PROXY_VOTES.remove(deps.storage, (poll_id, &delegator));
+    }
+    let mut response = Response::new();
+    for (member, power) in represented {
+        // Power already counted on another ballot moves to this one
+        if let Some(proxy) = PROXY_VOTES.may_load(deps.storage, (poll_id, &member))? {
+            response = remove_proxy_vote(response, deps.storage, &mut poll, poll_id, &proxy)?;
+            PROXY_VOTES.remove(deps.storage, (poll_id, &member));
+        }
+        if member != info.sender {
+            let proxy = ProxyVote { voter: info.sender.clone(), weight: power };
+            PROXY_VOTES.save(deps.storage, (poll_id, &member), &proxy)?;
+        }
+    }
+    let response =
+        record_ballot(response, deps.storage, &mut poll, info.sender, poll_id, &vote, weight)?;
 
+    // Save the update
+    polls().save(deps.storage, poll_id, &poll)?;
+    Ok(response
+        .add_attribute("action", "vote on poll")
+        .add_attribute("on:", vote.join(",")))
+}
 
-    match poll {
-        Some(mut poll) => {
-            // The poll exists
-            BALLOTS.update(
-                deps.storage,
-                (info.sender, &poll_id),
-                |ballot| -> StdResult<Ballot> {
-                    match ballot {
-                        Some(ballot) => {
-                            // We need to revoke their old vote
-                            // Find the position
-                            let position_of_old_vote = poll
-                                .options
-                                .iter()
-                                .position(|option| option.0 == ballot.option)
-                                .unwrap();
-                            // Decrement by 1
-                            poll.options[position_of_old_vote].1 -= 1;
-                            // Update the ballot
-                            Ok(Ballot {option: vote.clone()})
-                        }
-                        None => {
-                            // Simply add the ballot
-                            Ok(Ballot {option: vote.clone()})
-                        }
-                    }
-                },
-            )?;
-
-            // Find the position of the new vote option and increment it by 1
-            let position = poll
-                .options.iter()
-                .position(|option| option.0 == vote);
-            if position.is_none() {
-                return Err(ContractError::Unauthorized {});
-            }
-            let position = position.unwrap();
-            poll.options[position].1 += 1;
+// Casts one ballot per token, after checking with the collection that the sender owns each of them
+fn execute_nft_vote(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    poll_id: u64,
+    token_ids: Vec<String>,
+    vote: Vec<String>,
+) -> Result<Response, ContractError> {
+    let mut poll = polls()
+        .may_load(deps.storage, poll_id)?
+        .ok_or(ContractError::NoPoll {})?;
+    let collection = poll.nft.clone().ok_or(ContractError::NotNftPoll {})?;
+    ensure_open(&poll, &env)?;
+    validate_ballot(&poll, &vote)?;
+    if token_ids.is_empty() || has_duplicates(&token_ids) {
+        return Err(ContractError::InvalidTokenIds {});
+    }
 
-            // Save the update
-            POLLS.save(deps.storage, &poll_id, &poll)?;
-            Ok(Response::new()
-                .add_attribute("action", "vote on poll")
-                .add_attribute("on:", vote))
+    let mut response = Response::new();
+    for token_id in token_ids {
+        let query = Cw721QueryMsg::OwnerOf { token_id: token_id.clone(), include_expired: None };
+        let owner: OwnerOfResponse = deps.querier.query_wasm_smart(&collection, &query)?;
+        if owner.owner != info.sender {
+            return Err(ContractError::NotTokenOwner { token_id });
         }
-        None => Err(ContractError::NoPoll {}), // The poll does not exist
+
+        let key = (poll_id, token_id.as_str());
+        let previous = NFT_BALLOTS.may_load(deps.storage, key)?.map(|previous| previous.ballot);
+        let ballot = Ballot {
+            poll_id,
+            options: vote.clone(),
+            weight: Uint128::new(1),
+        };
+        let nft_ballot = NftBallot { voter: info.sender.clone(), ballot: ballot.clone() };
+        NFT_BALLOTS.save(deps.storage, key, &nft_ballot)?;
+        tally_ballot(&mut poll, previous.as_ref(), &ballot);
+        response = announce_ballot(
+            response,
+            deps.storage,
+            &poll,
+            &info.sender,
+            Some(&token_id),
+            previous.as_ref(),
+            &ballot,
+        )?;
     }
+
+    polls().save(deps.storage, poll_id, &poll)?;
+    Ok(response
+        .add_attribute("action", "vote on poll")
+        .add_attribute("on:", vote.join(",")))
 }
 
-fn close_poll(
+// Votes sent by a cw20 token contract along with tokens for the voter
+fn execute_receive(
     deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo, // Will get used to verify users who can close polls. Unimplemented.
-    poll_id: String,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
 ) -> Result<Response, ContractError> {
-    let option_poll = POLLS.may_load(deps.storage, &poll_id)?;
-    match option_poll {
-        Some(mut poll) => {
-            poll.closed= true;
-            POLLS.save(deps.storage, &poll_id, &poll)?;
-            Ok(Response::new().add_attribute("action", "closed poll"))
-            },
-        None => Err(ContractError::NoPoll {}),
+    let voter = deps.api.addr_validate(&wrapper.sender)?;
+    match from_binary(&wrapper.msg)? {
+        ReceiveMsg::Vote { poll_id, vote } => {
+            execute_token_vote(deps, env, info, voter, poll_id, vote, wrapper.amount)
+        }
     }
 }
 
-fn add_admin(
+// Escrows the tokens and votes with everything the voter has escrowed on the poll
+// info.sender is the token contract, so the poll has to be weighted by it
+fn execute_token_vote(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    addmin: String
+    voter: Addr,
+    poll_id: u64,
+    vote: Vec<String>,
+    amount: Uint128,
 ) -> Result<Response, ContractError> {
-    let mut first = CONFIG.load(deps.storage)?.admins;
-    if info.sender != first[0] {
-        return Err(ContractError::Unauthorized {})
+    let mut poll = polls()
+        .may_load(deps.storage, poll_id)?
+        .ok_or(ContractError::NoPoll {})?;
+    if poll.token.as_ref() != Some(&info.sender) {
+        return Err(ContractError::NotTokenPoll {});
     }
-    first.push(Addr::unchecked(addmin.as_str()));
-    let config = Config {
-        admins: first,
-    };
-    CONFIG.save(deps.storage, &config)?;
-    Ok(Response::new())
+    ensure_open(&poll, &env)?;
+    validate_ballot(&poll, &vote)?;
+
+    // Tokens sent with a revote add to the ones already escrowed
+    let weight = ESCROWS.update(deps.storage, (&voter, poll_id), |escrowed| -> StdResult<_> {
+        Ok(escrowed.unwrap_or_default() + amount)
+    })?;
+    let response =
+        record_ballot(Response::new(), deps.storage, &mut poll, voter, poll_id, &vote, weight)?;
+
+    polls().save(deps.storage, poll_id, &poll)?;
+    Ok(response
+        .add_attribute("action", "vote on poll")
+        .add_attribute("on:", vote.join(",")))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::AllPolls {} => query_all_polls(deps, env),
-        QueryMsg::Poll { poll_id } => query_poll(deps, env, poll_id),
-        QueryMsg::Vote { address, poll_id } => query_vote(deps, env, address, poll_id),
+// Tokens can be claimed once voting ends, without waiting for the poll to be closed
+fn claim_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    poll_id: u64,
+) -> Result<Response, ContractError> {
+    let poll = polls()
+        .may_load(deps.storage, poll_id)?
+        .ok_or(ContractError::NoPoll {})?;
+    let status = poll.status(&env.block);
+    let token = poll.token.ok_or(ContractError::NotTokenPoll {})?;
+    if status != PollStatus::Ended && status != PollStatus::Closed {
+        return Err(ContractError::PollNotClosed {});
     }
-}
+    let amount = ESCROWS
+        .may_load(deps.storage, (&info.sender, poll_id))?
+        .ok_or(ContractError::NoEscrow {})?;
+    ESCROWS.remove(deps.storage, (&info.sender, poll_id));
 
-fn query_all_polls(deps: Deps, _env: Env) -> StdResult<Binary> {
-    let polls = POLLS
-        .range(deps.storage, None, None, Order::Ascending)
-        .map(|p| Ok(p?.1))
-        .collect::<StdResult<Vec<_>>>()?;
+    let transfer = Cw20ExecuteMsg::Transfer { recipient: info.sender.to_string(), amount };
+    Ok(Response::new()
+        .add_event(events::tokens_claimed(poll_id, &info.sender, &token, amount))
+        .add_message(WasmMsg::Execute {
+            contract_addr: token.to_string(),
+            msg: to_binary(&transfer)?,
+            funds: vec![],
+        })
+        .add_attribute("action", "claim tokens")
+        .add_attribute("amount", amount.to_string()))
+}
 
-    to_binary(&AllPollsResponse { polls })
+// The voter followed by everyone whose power flows to them on the poll
+// Delegators with their own ballot are left out along with everyone delegating to them
+fn represented_voters(
+    storage: &dyn Storage,
+    poll: &Poll,
+    poll_id: u64,
+    voter: &Addr,
+) -> StdResult<Vec<Addr>> {
+    let mut members = vec![voter.clone()];
+    let mut i = 0;
+    while i < members.len() {
+        for delegator in delegators(storage, &members[i], &poll.category)? {
+            if !members.contains(&delegator) && !ballots().has(storage, (delegator.clone(), poll_id)) {
+                members.push(delegator);
+            }
+        }
+        i += 1;
+    }
+    Ok(members)
 }
 
-fn query_poll(deps: Deps, _env: Env, poll_id: String) -> StdResult<Binary> {
-    let poll = POLLS.may_load(deps.storage, &poll_id)?;
-    to_binary(&PollResponse { poll })
+// Addresses whose delegation for polls of the category goes to the delegate
+fn delegators(storage: &dyn Storage, delegate: &Addr, category: &Option<String>) -> StdResult<Vec<Addr>> {
+    let mut delegators: Vec<Addr> = vec![];
+    for key in delegations()
+        .idx
+        .delegate
+        .prefix(delegate.clone())
+        .keys(storage, None, None, Order::Ascending)
+    {
+        let (delegator, _) = key?;
+        if !delegators.contains(&delegator)
+            && effective_delegate(storage, &delegator, category)?.as_ref() == Some(delegate)
+        {
+            delegators.push(delegator);
+        }
+    }
+    Ok(delegators)
 }
 
-fn query_vote(deps: Deps, _env: Env, address: String, poll_id: String) -> StdResult<Binary> {
-    let validated_address = deps.api.addr_validate(&address)?;
-    let vote = BALLOTS.may_load(deps.storage, (validated_address, &poll_id))?;
-    to_binary(&VoteResponse { vote })
+// Who the address delegates to for polls of the category, a category delegation beats a global one
+fn effective_delegate(
+    storage: &dyn Storage,
+    address: &Addr,
+    category: &Option<String>,
+) -> StdResult<Option<Addr>> {
+    if let Some(category) = category {
+        let key = DelegationScope::Category(category.clone()).key();
+        if let Some(delegation) = delegations().may_load(storage, (address, &key))? {
+            return Ok(Some(delegation.delegate));
+        }
+    }
+    let key = DelegationScope::Global.key();
+    Ok(delegations().may_load(storage, (address, &key))?.map(|delegation| delegation.delegate))
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::contract::{execute, instantiate, query}; // the contract instantiate function
-    use crate::msg::{
-        AllPollsResponse, ExecuteMsg, InstantiateMsg, PollResponse, QueryMsg, VoteResponse,
+// Takes a delegator's power back off the ballot it was counted on
+// The delegate's ballot changes, so it is announced like any other changed vote
+fn remove_proxy_vote(
+    response: Response,
+    storage: &mut dyn Storage,
+    poll: &mut Poll,
+    poll_id: u64,
+    proxy: &ProxyVote,
+) -> StdResult<Response> {
+    let key = (proxy.voter.clone(), poll_id);
+    let previous = match ballots().may_load(storage, key.clone())? {
+        Some(previous) => previous,
+        None => return Ok(response),
     };
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info}; // mock functions to mock an environment, message info, dependencies
-    use cosmwasm_std::{attr, from_binary, Coin};
-    use crate::state::CONFIG;
-
-    // Two fake addresses we will use to mock_info
-    pub const ADDR1: &str = "addr1";
-    pub const ADDR2: &str = "addr2";
-
-    pub const LFG: &str = "addr3";
+    let ballot = Ballot { weight: previous.weight - proxy.weight, ..previous.clone() };
+    for option in tallied_options(&poll.poll_type, &ballot.options) {
+        let position = poll.options.iter().position(|tally| &tally.0 == option).unwrap();
+        poll.options[position].1 -= proxy.weight;
+    }
+    poll.turnout -= proxy.weight;
+    ballots().save(storage, key, &ballot)?;
+    announce_ballot(response, storage, poll, &proxy.voter, None, Some(&previous), &ballot)
+}
 
-    #[test]
-    fn test_instantiate() {
-        // Mock the dependencies, must be mutable so we can pass it as a mutable, empty vector means our contract has no balance
-        let mut deps = mock_dependencies();
-        // Mock the contract environment, contains the block info, contract address, etc.
-        let env = mock_env();
-        // Mock the message info, ADDR1 will be the sender, the empty vec means we sent no funds.
-        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+// Delegations are read when the delegate votes, ballots already cast keep their weight
+fn execute_delegate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    to: String,
+    scope: DelegationScope,
+) -> Result<Response, ContractError> {
+    let delegate = deps.api.addr_validate(&to)?;
+    // Delegations of every scope are followed, so no category can ever end up with a cycle
+    if delegates_reach(deps.storage, &delegate, &info.sender)? {
+        return Err(ContractError::DelegationCycle {});
+    }
+    let delegation = Delegation { delegate: delegate.clone(), scope };
+    delegations().save(deps.storage, (&info.sender, &delegation.scope.key()), &delegation)?;
+    Ok(Response::new()
+        .add_event(events::delegated(&info.sender, &delegate, &delegation.scope))
+        .add_attribute("action", "delegate")
+        .add_attribute("delegate", delegate)
+        .add_attribute("scope", delegation.scope.key()))
+}
 
-        // Create a message where we (the sender) will be an admin
-        let msg = InstantiateMsg { admin: None };
-        // Call instantiate, unwrap to assert success
-        let res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+fn execute_undelegate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    scope: DelegationScope,
+) -> Result<Response, ContractError> {
+    delegations().remove(deps.storage, (&info.sender, &scope.key()))?;
+    Ok(Response::new()
+        .add_event(events::undelegated(&info.sender, &scope))
+        .add_attribute("action", "undelegate")
+        .add_attribute("scope", scope.key()))
+}
 
-        
+// Whether target can be reached from the address by following delegations of any scope
+fn delegates_reach(storage: &dyn Storage, address: &Addr, target: &Addr) -> StdResult<bool> {
+    let mut queue = vec![address.clone()];
+    let mut seen: Vec<Addr> = vec![];
+    while let Some(next) = queue.pop() {
+        if &next == target {
+            return Ok(true);
+        }
+        if seen.contains(&next) {
+            continue;
+        }
+        for item in delegations().prefix(&next).range(storage, None, None, Order::Ascending) {
+            let (_, delegation) = item?;
+            queue.push(delegation.delegate);
+        }
+        seen.push(next);
+    }
+    Ok(false)
+}
 
-        assert_eq!(
-            res.attributes,
-            vec![attr("action", "instantiate"), attr("admin", ADDR1)]
-        )
+// Commits to a secret ballot, nothing is tallied until it is revealed
+fn execute_commit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    poll_id: u64,
+    commitment: Binary,
+) -> Result<Response, ContractError> {
+    let mut poll = polls()
+        .may_load(deps.storage, poll_id)?
+        .ok_or(ContractError::NoPoll {})?;
+    if poll.reveal_end.is_none() {
+        return Err(ContractError::NotCommitRevealPoll {});
     }
-    #[test]
-    fn test_instantiate_with_admin() {
-        // Mock the dependencies, must be mutable so we can pass it as a mutable, empty vector means our contract has no balance
-        let mut deps = mock_dependencies();
-        // Mock the contract environment, contains the block info, contract address, etc.
-        let env = mock_env();
-        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
-        // Create a message where ADDR2 will be the admin, not the sender (ADDR1)
-        let msg = InstantiateMsg {
-            admin: Some(ADDR2.to_string()),
-        };
-        let res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+    ensure_open(&poll, &env)?;
 
-        assert_eq!(
-            res.attributes,
-            vec![attr("action", "instantiate"), attr("admin", ADDR2)],
-        )
+    let weight = voter_weight(deps.as_ref(), &poll, &info.sender)?;
+    // Committing again replaces the old commitment
+    let key = (info.sender.clone(), poll_id);
+    if let Some(commit) = COMMITS.may_load(deps.storage, key.clone())? {
+        poll.unrevealed -= commit.weight;
     }
-    #[test]
-    fn test_execute_create_poll_valid() {
-        let mut deps = mock_dependencies();
-        let env = mock_env();
-        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
-        // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
-        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    COMMITS.save(deps.storage, key, &Commit { commitment, weight })?;
+    poll.unrevealed += weight;
 
-        // New execute msg
-        let msg = ExecuteMsg::CreatePoll {
-            poll_id: "some_id".to_string(),
-            question: "What's your favourite Cosmos coin?".to_string(),
-            options: vec![
-                "Cosmos Hub".to_string(),
-                "Juno".to_string(),
-                "Osmosis".to_string(),
-            ],
-        };
+    polls().save(deps.storage, poll_id, &poll)?;
+    Ok(Response::new()
+        .add_event(events::vote_committed(poll_id, &info.sender, weight))
+        .add_attribute("action", "commit vote"))
+}
 
-        // Unwrap to assert success
-        let _res = execute(deps.as_mut(), env, info, msg).unwrap();
+// Checks a revealed ballot against its commitment and tallies it with the committed weight
+fn execute_reveal(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    poll_id: u64,
+    vote: Vec<String>,
+    salt: String,
+) -> Result<Response, ContractError> {
+    let mut poll = polls()
+        .may_load(deps.storage, poll_id)?
+        .ok_or(ContractError::NoPoll {})?;
+    if poll.reveal_end.is_none() {
+        return Err(ContractError::NotCommitRevealPoll {});
+    }
+    if poll.status(&env.block) != PollStatus::Revealing {
+        return Err(ContractError::NotRevealing {});
+    }
 
-        assert_eq!(_res.attributes, vec![attr("action", "create poll")],)
+    let key = (info.sender.clone(), poll_id);
+    let commit = COMMITS
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::NoCommit {})?;
+    if commitment(poll_id, &info.sender, &vote, &salt)? != commit.commitment {
+        return Err(ContractError::InvalidReveal {});
     }
+    validate_ballot(&poll, &vote)?;
 
-    #[test]
-    fn test_check_poll_closed(){
-        let mut deps = mock_dependencies();
-        let env = mock_env();
-        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
-        // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
-        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    COMMITS.remove(deps.storage, key);
+    poll.unrevealed -= commit.weight;
+    let response = record_ballot(
+        Response::new(),
+        deps.storage,
+        &mut poll,
+        info.sender,
+        poll_id,
+        &vote,
+        commit.weight,
+    )?;
 
-        // New execute msg
-        let msg = ExecuteMsg::CreatePoll {
-            poll_id: "some_id".to_string(),
-            question: "What's your favourite Cosmos coin?".to_string(),
-            options: vec![
-                "Cosmos Hub".to_string(),
-                "Juno".to_string(),
-                "Osmosis".to_string(),
-            ],
-        };
+    polls().save(deps.storage, poll_id, &poll)?;
+    Ok(response
+        .add_attribute("action", "reveal vote")
+        .add_attribute("on:", vote.join(",")))
+}
 
-        // Unwrap to assert success
-        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
-        
-        let msg = ExecuteMsg::ClosePoll { poll_id: ("some_id".to_string()) };
+fn ensure_open(poll: &Poll, env: &Env) -> Result<(), ContractError> {
+    match poll.status(&env.block) {
+        PollStatus::Open => Ok(()),
+        PollStatus::Pending => Err(ContractError::PollNotStarted {}),
+        PollStatus::Revealing | PollStatus::Ended | PollStatus::Closed => {
+            Err(ContractError::ClosedPoll {})
+        }
+    }
+}
 
-        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+fn voter_weight(deps: Deps, poll: &Poll, voter: &Addr) -> Result<Uint128, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let weight = voting_power(deps, &config, poll, voter)?;
+    if weight.is_zero() {
+        return Err(no_power(poll));
+    }
+    Ok(weight)
+}
 
-        println!("Output for ClosePoll message: {res:?}");
+// The error for a voter without any power on the poll
+fn no_power(poll: &Poll) -> ContractError {
+    match poll.group {
+        Some(_) => ContractError::NotMember {},
+        None => ContractError::NotStaked {},
+    }
+}
 
+// Check the ballot fits the poll
+fn validate_ballot(poll: &Poll, vote: &[String]) -> Result<(), ContractError> {
+    for choice in vote {
+        if !poll.options.iter().any(|option| &option.0 == choice) {
+            return Err(ContractError::InvalidOption { option: choice.clone() });
+        }
+    }
+    if vote.is_empty() {
+        return Err(ContractError::InvalidBallot {});
+    }
+    match poll.poll_type {
+        PollType::Plurality => {
+            if vote.len() != 1 {
+                return Err(ContractError::InvalidBallot {});
+            }
+        }
+        PollType::RankedChoice => {
+            if has_duplicates(vote) {
+                return Err(ContractError::InvalidBallot {});
+            }
+        }
+        PollType::Approval { max_selections } => {
+            if has_duplicates(vote) {
+                return Err(ContractError::InvalidBallot {});
+            }
+            if vote.len() > max_selections as usize {
+                return Err(ContractError::TooManySelections { max_selections });
+            }
+        }
+        PollType::Quadratic { credits } => {
+            // Quadratic ballots repeat an option once per vote
+            let cost = quadratic_cost(vote);
+            if cost > credits {
+                return Err(ContractError::NotEnoughCredits { cost, credits });
+            }
+        }
+    }
+    Ok(())
+}
 
-        let msg = ExecuteMsg::Vote {
-            poll_id: "some_id".to_string(),
-            vote: "Osmosis".to_string(),
-        };
+// Saves the ballot and tallies it on the poll, taking back the voter's previous ballot
+// Adds the vote event and hook messages to the response
+fn record_ballot(
+    response: Response,
+    storage: &mut dyn Storage,
+    poll: &mut Poll,
+    voter: Addr,
+    poll_id: u64,
+    vote: &[String],
+    weight: Uint128,
+) -> StdResult<Response> {
+    let key = (voter.clone(), poll_id);
+    let previous = ballots().may_load(storage, key.clone())?;
+    let ballot = Ballot {
+        poll_id,
+        options: vote.to_vec(),
+        weight,
+    };
+    ballots().save(storage, key, &ballot)?;
+    tally_ballot(poll, previous.as_ref(), &ballot);
+    announce_ballot(response, storage, poll, &voter, None, previous.as_ref(), &ballot)
+}
 
-        let res = execute(deps.as_mut(), env, info, msg);
-        
-        println!("Output for voting on a closed poll: {res:?}");
+// Takes the previous ballot off the poll's tallies and counts the new one
+fn tally_ballot(poll: &mut Poll, previous: Option<&Ballot>, ballot: &Ballot) {
+    // If they voted before we need to revoke their old vote
+    if let Some(previous) = previous {
+        for old_vote in tallied_options(&poll.poll_type, &previous.options) {
+            let position_of_old_vote = poll
+                .options
+                .iter()
+                .position(|option| &option.0 == old_vote)
+                .unwrap();
+            // Decrement by the weight it was cast with
+            poll.options[position_of_old_vote].1 -= previous.weight;
+        }
+        poll.turnout -= previous.weight;
+    }
 
+    // Find the position of each new vote option and increment it by the voter's weight
+    for new_vote in tallied_options(&poll.poll_type, &ballot.options) {
+        let position = poll
+            .options
+            .iter()
+            .position(|option| &option.0 == new_vote)
+            .unwrap();
+        poll.options[position].1 += ballot.weight;
     }
+    poll.turnout += ballot.weight;
+}
 
-    #[test]
+// Announces the ballot to indexers and hook contracts
+fn announce_ballot(
+    response: Response,
+    storage: &dyn Storage,
+    poll: &Poll,
+    voter: &Addr,
+    token_id: Option<&str>,
+    previous: Option<&Ballot>,
+    ballot: &Ballot,
+) -> StdResult<Response> {
+    let hook = VoteHookMsg {
+        poll_id: ballot.poll_id,
+        voter: voter.to_string(),
+        token_id: token_id.map(String::from),
+        previous: previous.map(|previous| previous.options.clone()),
+        options: ballot.options.clone(),
+        weight: ballot.weight,
+    };
+    let event = events::vote(ballot.poll_id, voter, token_id, previous, ballot, poll)?;
+    Ok(response
+        .add_submessages(prepare_hooks(storage, HookExecuteMsg::VoteHook(hook))?)
+        .add_event(event))
+}
+
+// A voter's weight is their total delegated stake
+// When the config lists validators only delegations to those validators count
+// Polls with a group use the voter's member weight at the height the poll was created instead,
+// so joining or leaving the group mid-poll doesn't change who can vote
+fn voting_power(deps: Deps, config: &Config, poll: &Poll, voter: &Addr) -> StdResult<Uint128> {
+    if let Some(group) = &poll.group {
+        let query = Cw4QueryMsg::Member { addr: voter.to_string(), at_height: Some(group.height) };
+        let member: MemberResponse = deps.querier.query_wasm_smart(&group.addr, &query)?;
+        return Ok(Uint128::from(member.weight.unwrap_or_default()));
+    }
+    let delegations = deps.querier.query_all_delegations(voter)?;
+    Ok(delegations
+        .into_iter()
+        .filter(|delegation| match &config.validators {
+            Some(validators) => validators.contains(&delegation.validator),
+            None => true,
+        })
+        .map(|delegation| delegation.amount.amount)
+        .sum())
+}
+
+// The options of a ballot that are tallied on the poll
+// Approval and quadratic ballots count every entry, the others only count their first option
+fn tallied_options<'a>(poll_type: &PollType, options: &'a [String]) -> &'a [String] {
+    match poll_type {
+        PollType::Approval { .. } | PollType::Quadratic { .. } => options,
+        _ => &options[..1],
+    }
+}
+
+fn has_duplicates(options: &[String]) -> bool {
+    options
+        .iter()
+        .enumerate()
+        .any(|(i, option)| options[..i].contains(option))
+}
+
+fn close_poll(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    poll_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.storage, &info.sender, Role::PollCloser)?;
+    let mut poll = polls()
+        .may_load(deps.storage, poll_id)?
+        .ok_or(ContractError::NoPoll {})?;
+    if poll.closed {
+        return Err(ContractError::ClosedPoll {});
+    }
+    if poll.tallies_hidden(&env.block) {
+        return Err(ContractError::RevealNotEnded {});
+    }
+
+    let mut runoff = None;
+    if poll.poll_type == PollType::RankedChoice {
+        // Collect every ranking cast on this poll and run the instant-runoff
+        // NFT gated polls keep a ballot per token instead of per voter
+        let ballots = match poll.nft {
+            Some(_) => NFT_BALLOTS
+                .prefix(poll_id)
+                .range(deps.storage, None, None, Order::Ascending)
+                .map(|item| item.map(|(_, nft_ballot)| nft_ballot.ballot))
+                .collect::<StdResult<Vec<_>>>()?,
+            None => ballots()
+                .idx
+                .poll
+                .prefix(poll_id)
+                .range(deps.storage, None, None, Order::Ascending)
+                .map(|item| item.map(|(_, ballot)| ballot))
+                .collect::<StdResult<Vec<_>>>()?,
+        };
+        let options: Vec<String> = poll.options.iter().map(|option| option.0.clone()).collect();
+        let result = instant_runoff(&options, &ballots);
+        RUNOFFS.save(deps.storage, poll_id, &result)?;
+        runoff = Some(result);
+    }
+
+    let result = poll_result(&poll, runoff.as_ref());
+    poll.closed = true;
+    poll.result = Some(result.clone());
+    polls().save(deps.storage, poll_id, &poll)?;
+
+    let hook = PollHookMsg::Closed {
+        poll_id,
+        outcome: result.outcome,
+        winner: result.winner.clone(),
+    };
+    let mut response = Response::new()
+        .add_submessages(prepare_hooks(deps.storage, HookExecuteMsg::PollHook(hook))?)
+        .add_event(events::poll_closed(poll_id, &poll, &result)?);
+    // Refund the deposit, or take it if the poll missed quorum or was vetoed
+    if let Some(deposit) = poll.deposit {
+        match result.outcome {
+            Outcome::NoQuorum | Outcome::Vetoed => {
+                if poll.burn {
+                    response = response.add_message(BankMsg::Burn { amount: vec![deposit] });
+                } else {
+                    TREASURY.update(deps.storage, &deposit.denom, |balance| -> StdResult<_> {
+                        Ok(balance.unwrap_or_default() + deposit.amount)
+                    })?;
+                }
+            }
+            Outcome::Passed | Outcome::Rejected => {
+                response = response.add_message(BankMsg::Send {
+                    to_address: poll.creator.to_string(),
+                    amount: vec![deposit],
+                });
+            }
+        }
+    }
+    Ok(response
+        .add_attribute("action", "closed poll")
+        .add_attribute("winner", result.winner.unwrap_or_default()))
+}
+
+// Dispatches the messages of a closed poll whose winner is its first (yes) option
+fn execute_poll_msgs(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    poll_id: u64,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.storage, &info.sender, Role::PollCloser)?;
+    let mut poll = polls()
+        .may_load(deps.storage, poll_id)?
+        .ok_or(ContractError::NoPoll {})?;
+    if poll.executed {
+        return Err(ContractError::AlreadyExecuted {});
+    }
+    match &poll.result {
+        Some(PollResult { outcome: Outcome::Passed, winner: Some(winner) })
+            if !poll.msgs.is_empty() && winner == &poll.options[0].0 => {}
+        _ => return Err(ContractError::NotPassed {}),
+    }
+
+    // The messages can only spend the treasury, deposits and escrows stay untouched
+    check_escrowed_tokens(deps.storage, &poll.msgs)?;
+    let spent: Vec<Coin> = poll.msgs.iter().flat_map(spent_funds).collect();
+    for coin in &spent {
+        let available = TREASURY.may_load(deps.storage, &coin.denom)?.unwrap_or_default();
+        if coin.amount > available {
+            let denom = coin.denom.clone();
+            return Err(ContractError::InsufficientTreasury { available, denom });
+        }
+        TREASURY.save(deps.storage, &coin.denom, &(available - coin.amount))?;
+    }
+
+    poll.executed = true;
+    polls().save(deps.storage, poll_id, &poll)?;
+    Ok(Response::new()
+        .add_event(events::poll_executed(poll_id, poll.msgs.len(), &spent)?)
+        .add_messages(poll.msgs)
+        .add_attribute("action", "execute poll")
+        .add_attribute("poll_id", poll_id.to_string()))
+}
+
+// Rejects messages to a contract holding tokens escrowed by voters
+fn check_escrowed_tokens(storage: &dyn Storage, msgs: &[CosmosMsg]) -> Result<(), ContractError> {
+    for msg in msgs {
+        let contract = match msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. })
+            | CosmosMsg::Wasm(WasmMsg::Migrate { contract_addr, .. })
+            | CosmosMsg::Wasm(WasmMsg::UpdateAdmin { contract_addr, .. })
+            | CosmosMsg::Wasm(WasmMsg::ClearAdmin { contract_addr }) => contract_addr,
+            _ => continue,
+        };
+        if ESCROW_TOKENS.has(storage, &Addr::unchecked(contract)) {
+            return Err(ContractError::EscrowedToken {});
+        }
+    }
+    Ok(())
+}
+
+// Native funds a message takes out of the contract's balance
+fn spent_funds(msg: &CosmosMsg) -> Vec<Coin> {
+    match msg {
+        CosmosMsg::Bank(BankMsg::Send { amount, .. }) | CosmosMsg::Bank(BankMsg::Burn { amount }) => {
+            amount.clone()
+        }
+        CosmosMsg::Wasm(WasmMsg::Execute { funds, .. })
+        | CosmosMsg::Wasm(WasmMsg::Instantiate { funds, .. }) => funds.clone(),
+        CosmosMsg::Staking(StakingMsg::Delegate { amount, .. }) => vec![amount.clone()],
+        _ => vec![],
+    }
+}
+
+fn add_admin(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    addmin: String
+) -> Result<Response, ContractError> {
+    grant_role(deps, env, info, addmin, Role::PollCreator)
+}
+
+// Checks the address holds the role, owners hold every role
+fn ensure_role(storage: &dyn Storage, address: &Addr, role: Role) -> Result<(), ContractError> {
+    if ROLES.has(storage, (address, Role::Owner)) || ROLES.has(storage, (address, role)) {
+        Ok(())
+    } else {
+        Err(ContractError::MissingRole { role: role.as_str().to_string() })
+    }
+}
+
+// Owners manage every role, moderators only manage poll creators and closers
+fn ensure_can_manage(storage: &dyn Storage, sender: &Addr, role: Role) -> Result<(), ContractError> {
+    match role {
+        Role::PollCreator | Role::PollCloser => ensure_role(storage, sender, Role::Moderator),
+        _ => ensure_role(storage, sender, Role::Owner),
+    }
+}
+
+fn grant_role(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String,
+    role: Role,
+) -> Result<Response, ContractError> {
+    ensure_can_manage(deps.storage, &info.sender, role)?;
+    if role == Role::Owner {
+        return Err(ContractError::OwnerRole {});
+    }
+    let address = deps.api.addr_validate(&address)?;
+    ROLES.save(deps.storage, (&address, role), &())?;
+    Ok(Response::new()
+        .add_event(events::admin_changed(&address, role, true))
+        .add_attribute("action", "grant role")
+        .add_attribute("address", address)
+        .add_attribute("role", role.as_str()))
+}
+
+fn revoke_role(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String,
+    role: Role,
+) -> Result<Response, ContractError> {
+    ensure_can_manage(deps.storage, &info.sender, role)?;
+    if role == Role::Owner {
+        return Err(ContractError::OwnerRole {});
+    }
+    let address = deps.api.addr_validate(&address)?;
+    ROLES.remove(deps.storage, (&address, role));
+    Ok(Response::new()
+        .add_event(events::admin_changed(&address, role, false))
+        .add_attribute("action", "revoke role")
+        .add_attribute("address", address)
+        .add_attribute("role", role.as_str()))
+}
+
+fn remove_admin(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.storage, &info.sender, Role::Owner)?;
+    let address = deps.api.addr_validate(&address)?;
+    let roles = ROLES
+        .prefix(&address)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut response = Response::new();
+    for role in roles.into_iter().filter(|role| role != &Role::Owner) {
+        ROLES.remove(deps.storage, (&address, role));
+        response = response.add_event(events::admin_changed(&address, role, false));
+    }
+    Ok(response
+        .add_attribute("action", "remove admin")
+        .add_attribute("address", address))
+}
+
+fn propose_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_owner: String,
+    expiry: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.storage, &info.sender, Role::Owner)?;
+    let address = deps.api.addr_validate(&new_owner)?;
+    let expires = expiry.unwrap_or_else(|| Expiration::AtTime(env.block.time.plus_seconds(86400)));
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::TransferExpired {});
+    }
+    // A new proposal replaces any pending one
+    PENDING_OWNER.save(deps.storage, &PendingOwner { address: address.clone(), expires })?;
+    Ok(Response::new()
+        .add_event(events::ownership_proposed(&address, &expires))
+        .add_attribute("action", "propose owner")
+        .add_attribute("pending_owner", address)
+        .add_attribute("expires", expires.to_string()))
+}
+
+fn accept_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let pending = PENDING_OWNER
+        .may_load(deps.storage)?
+        .filter(|pending| pending.address == info.sender)
+        .ok_or(ContractError::NoPendingOwner {})?;
+    if pending.expires.is_expired(&env.block) {
+        return Err(ContractError::TransferExpired {});
+    }
+
+    let mut response = Response::new();
+    if let Some(owner) = owner(deps.storage)? {
+        ROLES.remove(deps.storage, (&owner, Role::Owner));
+        response = response.add_event(events::admin_changed(&owner, Role::Owner, false));
+    }
+    ROLES.save(deps.storage, (&info.sender, Role::Owner), &())?;
+    PENDING_OWNER.remove(deps.storage);
+    Ok(response
+        .add_event(events::admin_changed(&info.sender, Role::Owner, true))
+        .add_attribute("action", "accept ownership")
+        .add_attribute("owner", info.sender))
+}
+
+fn renounce_ownership(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.storage, &info.sender, Role::Owner)?;
+    ROLES.remove(deps.storage, (&info.sender, Role::Owner));
+    PENDING_OWNER.remove(deps.storage);
+    Ok(Response::new()
+        .add_event(events::admin_changed(&info.sender, Role::Owner, false))
+        .add_attribute("action", "renounce ownership"))
+}
+
+// The address holding the owner role, if it hasn't been renounced
+fn owner(storage: &dyn Storage) -> StdResult<Option<Addr>> {
+    let owners = role_holders(storage, Role::Owner)?;
+    Ok(owners.into_iter().next())
+}
+
+fn role_holders(storage: &dyn Storage, role: Role) -> StdResult<Vec<Addr>> {
+    ROLES
+        .keys(storage, None, None, Order::Ascending)
+        .filter_map(|key| match key {
+            Ok((address, held)) if held == role => Some(Ok(address)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect()
+}
+
+fn update_config(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    fees: Option<Fees>,
+    deposit: Option<DepositConfig>,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.storage, &info.sender, Role::FeeManager)?;
+    let mut config = CONFIG.load(deps.storage)?;
+    if let Some(fees) = fees {
+        config.fees = fees;
+    }
+    if let Some(deposit) = deposit {
+        validate_deposit(&deposit)?;
+        config.deposit = Some(deposit);
+    }
+    CONFIG.save(deps.storage, &config)?;
+    Ok(Response::new()
+        .add_event(events::config_updated(&config)?)
+        .add_attribute("action", "update config"))
+}
+
+// A veto threshold of 0 would let one vote veto a poll, and above 1 no poll could be vetoed
+fn validate_deposit(deposit: &DepositConfig) -> Result<(), ContractError> {
+    if let Some(veto) = &deposit.veto {
+        if veto.threshold.is_zero() || veto.threshold > Decimal::one() {
+            return Err(ContractError::InvalidThreshold {});
+        }
+    }
+    Ok(())
+}
+
+fn withdraw_fees(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    amount: Coin,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.storage, &info.sender, Role::FeeManager)?;
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    let available = TREASURY
+        .may_load(deps.storage, &amount.denom)?
+        .unwrap_or_default();
+    if amount.amount > available {
+        return Err(ContractError::InsufficientTreasury { available, denom: amount.denom });
+    }
+    TREASURY.save(deps.storage, &amount.denom, &(available - amount.amount))?;
+
+    Ok(Response::new()
+        .add_event(events::fees_withdrawn(&recipient, &amount))
+        .add_message(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![amount.clone()],
+        })
+        .add_attribute("action", "withdraw fees")
+        .add_attribute("recipient", recipient)
+        .add_attribute("amount", amount.to_string()))
+}
+
+fn add_hook(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.storage, &info.sender, Role::Owner)?;
+    let addr = deps.api.addr_validate(&addr)?;
+    let mut hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    if hooks.contains(&addr) {
+        return Err(ContractError::HookAlreadyRegistered {});
+    }
+    hooks.push(addr.clone());
+    HOOKS.save(deps.storage, &hooks)?;
+    Ok(Response::new()
+        .add_event(events::hook_changed(&addr, true))
+        .add_attribute("action", "add hook")
+        .add_attribute("hook", addr))
+}
+
+fn remove_hook(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.storage, &info.sender, Role::Owner)?;
+    let addr = deps.api.addr_validate(&addr)?;
+    let mut hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    if !hooks.contains(&addr) {
+        return Err(ContractError::HookNotRegistered {});
+    }
+    hooks.retain(|hook| hook != &addr);
+    HOOKS.save(deps.storage, &hooks)?;
+    Ok(Response::new()
+        .add_event(events::hook_changed(&addr, false))
+        .add_attribute("action", "remove hook")
+        .add_attribute("hook", addr))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::AllPolls {
+            start_after,
+            limit,
+            closed,
+            creator,
+            created_after,
+            created_before,
+        } => query_all_polls(
+            deps, env, start_after, limit, closed, creator, created_after, created_before,
+        ),
+        QueryMsg::Poll { poll_id } => query_poll(deps, env, poll_id),
+        QueryMsg::ExternalRef { external_ref } => query_external_ref(deps, env, external_ref),
+        QueryMsg::Vote { address, poll_id } => query_vote(deps, env, address, poll_id),
+        QueryMsg::Ballots { poll_id, start_after, limit } => {
+            query_ballots(deps, env, poll_id, start_after, limit)
+        }
+        QueryMsg::NftBallots { poll_id, start_after, limit } => {
+            query_nft_ballots(deps, env, poll_id, start_after, limit)
+        }
+        QueryMsg::VoterBallots { address, start_after, limit } => {
+            query_voter_ballots(deps, env, address, start_after, limit)
+        }
+        QueryMsg::Runoff { poll_id } => query_runoff(deps, env, poll_id),
+        QueryMsg::Credits { poll_id, address, token_id } => {
+            query_credits(deps, env, poll_id, address, token_id)
+        }
+        QueryMsg::Config {} => query_config(deps, env),
+        QueryMsg::Treasury {} => query_treasury(deps, env),
+        QueryMsg::Hooks {} => query_hooks(deps, env),
+        QueryMsg::NftVote { poll_id, token_id } => query_nft_vote(deps, env, poll_id, token_id),
+        QueryMsg::Escrow { poll_id, address } => query_escrow(deps, env, poll_id, address),
+        QueryMsg::RoleHolders { role } => query_role_holders(deps, env, role),
+        QueryMsg::Admins {} => query_admins(deps, env),
+        QueryMsg::Ownership {} => query_ownership(deps, env),
+        QueryMsg::DelegationChain { address, category } => {
+            query_delegation_chain(deps, env, address, category)
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn query_all_polls(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    closed: Option<bool>,
+    creator: Option<String>,
+    created_after: Option<Timestamp>,
+    created_before: Option<Timestamp>,
+) -> StdResult<Binary> {
+    // An empty page would look like the end of the list, so pages hold at least one poll
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT) as usize;
+    let creator = creator.map(|creator| deps.api.addr_validate(&creator)).transpose()?;
+
+    // Narrow the range down with an index when filtering by creator or closed
+    let min = start_after.map(Bound::exclusive);
+    let range = match (&creator, closed) {
+        (Some(creator), _) => polls().idx.creator.prefix(creator.clone()).range(
+            deps.storage,
+            min,
+            None,
+            Order::Ascending,
+        ),
+        (None, Some(closed)) => polls().idx.closed.prefix(closed as u8).range(
+            deps.storage,
+            min,
+            None,
+            Order::Ascending,
+        ),
+        (None, None) => polls().range(deps.storage, min, None, Order::Ascending),
+    };
+
+    let mut polls: Vec<PollInfo> = vec![];
+    let mut last_scanned = None;
+    let mut next_start_after = None;
+    for (scanned, item) in range.enumerate() {
+        let (poll_id, poll) = item?;
+        // Ids follow creation order, so every poll from here on was created too late
+        if created_before.map_or(false, |time| poll.created >= time) {
+            break;
+        }
+        if polls.len() == limit || scanned == MAX_SCAN {
+            next_start_after = last_scanned;
+            break;
+        }
+        last_scanned = Some(poll_id);
+        if closed.map_or(true, |closed| poll.closed == closed)
+            && created_after.map_or(true, |time| poll.created > time)
+        {
+            let status = poll.status(&env.block);
+            let poll = public_view(poll, &env);
+            polls.push(PollInfo { poll_id, poll, status });
+        }
+    }
+
+    to_binary(&AllPollsResponse { polls, next_start_after })
+}
+
+fn query_poll(deps: Deps, env: Env, poll_id: u64) -> StdResult<Binary> {
+    let poll = polls().may_load(deps.storage, poll_id)?;
+    let status = poll.as_ref().map(|poll| poll.status(&env.block));
+    let poll = poll.map(|poll| public_view(poll, &env));
+    to_binary(&PollResponse { poll, status })
+}
+
+// Zeroes the tallies of a secret ballot poll until its reveal phase ends
+fn public_view(mut poll: Poll, env: &Env) -> Poll {
+    if poll.tallies_hidden(&env.block) {
+        for option in poll.options.iter_mut() {
+            option.1 = Uint128::zero();
+        }
+        poll.turnout = Uint128::zero();
+    }
+    poll
+}
+
+fn query_external_ref(deps: Deps, _env: Env, external_ref: String) -> StdResult<Binary> {
+    let poll_id = EXTERNAL_REFS.may_load(deps.storage, &external_ref)?;
+    to_binary(&ExternalRefResponse { poll_id })
+}
+
+fn query_vote(deps: Deps, _env: Env, address: String, poll_id: u64) -> StdResult<Binary> {
+    let validated_address = deps.api.addr_validate(&address)?;
+    let vote = ballots().may_load(deps.storage, (validated_address, poll_id))?;
+    to_binary(&VoteResponse { vote })
+}
+
+fn query_ballots(
+    deps: Deps,
+    _env: Env,
+    poll_id: u64,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after
+        .map(|address| deps.api.addr_validate(&address))
+        .transpose()?;
+    let min = start_after.map(|address| Bound::exclusive((address, poll_id)));
+    let ballots = ballots()
+        .idx
+        .poll
+        .prefix(poll_id)
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|((address, _), ballot)| (address, ballot)))
+        .collect::<StdResult<Vec<_>>>()?;
+    to_binary(&BallotsResponse { ballots })
+}
+
+fn query_nft_ballots(
+    deps: Deps,
+    _env: Env,
+    poll_id: u64,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let min = start_after.as_deref().map(Bound::exclusive);
+    let ballots = NFT_BALLOTS
+        .prefix(poll_id)
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    to_binary(&NftBallotsResponse { ballots })
+}
+
+fn query_voter_ballots(
+    deps: Deps,
+    env: Env,
+    address: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let validated_address = deps.api.addr_validate(&address)?;
+    let min = start_after.map(Bound::exclusive);
+    let ballots = ballots()
+        .prefix(validated_address)
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (poll_id, ballot) = item?;
+            let status = polls().load(deps.storage, poll_id)?.status(&env.block);
+            Ok(VoterBallot { poll_id, ballot, status })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    to_binary(&VoterBallotsResponse { ballots })
+}
+
+fn query_runoff(deps: Deps, _env: Env, poll_id: u64) -> StdResult<Binary> {
+    let runoff = RUNOFFS.may_load(deps.storage, poll_id)?;
+    to_binary(&RunoffResponse { runoff })
+}
+
+fn query_config(deps: Deps, _env: Env) -> StdResult<Binary> {
+    let config = CONFIG.load(deps.storage)?;
+    to_binary(&ConfigResponse { config })
+}
+
+fn query_treasury(deps: Deps, _env: Env) -> StdResult<Binary> {
+    let balances = TREASURY
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, amount) = item?;
+            Ok(Coin { denom, amount })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    to_binary(&TreasuryResponse { balances })
+}
+
+fn query_nft_vote(deps: Deps, _env: Env, poll_id: u64, token_id: String) -> StdResult<Binary> {
+    let vote = NFT_BALLOTS.may_load(deps.storage, (poll_id, &token_id))?;
+    to_binary(&NftVoteResponse { vote })
+}
+
+fn query_escrow(deps: Deps, _env: Env, poll_id: u64, address: String) -> StdResult<Binary> {
+    let address = deps.api.addr_validate(&address)?;
+    let amount = ESCROWS.may_load(deps.storage, (&address, poll_id))?.unwrap_or_default();
+    to_binary(&EscrowResponse { amount })
+}
+
+fn query_hooks(deps: Deps, _env: Env) -> StdResult<Binary> {
+    let hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    to_binary(&HooksResponse { hooks: hooks.into_iter().map(String::from).collect() })
+}
+
+fn query_role_holders(deps: Deps, _env: Env, role: Role) -> StdResult<Binary> {
+    let holders = role_holders(deps.storage, role)?;
+    to_binary(&RoleHoldersResponse { holders })
+}
+
+fn query_admins(deps: Deps, _env: Env) -> StdResult<Binary> {
+    // Keys are ordered by address so each admin's roles are next to each other
+    let mut admins: Vec<AdminInfo> = vec![];
+    for key in ROLES.keys(deps.storage, None, None, Order::Ascending) {
+        let (address, role) = key?;
+        match admins.last_mut() {
+            Some(admin) if admin.address == address => admin.roles.push(role),
+            _ => admins.push(AdminInfo { address, roles: vec![role] }),
+        }
+    }
+    to_binary(&AdminsResponse { admins })
+}
+
+fn query_ownership(deps: Deps, _env: Env) -> StdResult<Binary> {
+    let owner = owner(deps.storage)?;
+    let pending_owner = PENDING_OWNER.may_load(deps.storage)?;
+    to_binary(&OwnershipResponse { owner, pending_owner })
+}
+
+fn query_delegation_chain(
+    deps: Deps,
+    _env: Env,
+    address: String,
+    category: Option<String>,
+) -> StdResult<Binary> {
+    let mut address = deps.api.addr_validate(&address)?;
+    let mut chain: Vec<Addr> = vec![];
+    // Delegate refuses cycles, the check just keeps the loop bounded
+    while let Some(delegate) = effective_delegate(deps.storage, &address, &category)? {
+        if chain.contains(&delegate) {
+            break;
+        }
+        chain.push(delegate.clone());
+        address = delegate;
+    }
+    to_binary(&DelegationChainResponse { chain })
+}
+
+fn query_credits(
+    deps: Deps,
+    _env: Env,
+    poll_id: u64,
+    address: String,
+    token_id: Option<String>,
+) -> StdResult<Binary> {
+    let validated_address = deps.api.addr_validate(&address)?;
+    let remaining = match polls().may_load(deps.storage, poll_id)? {
+        Some(Poll { poll_type: PollType::Quadratic { credits }, nft, .. }) => {
+            let ballot = match nft {
+                Some(_) => {
+                    let token_id = token_id
+                        .ok_or_else(|| StdError::generic_err("NFT gated polls need a token_id"))?;
+                    NFT_BALLOTS
+                        .may_load(deps.storage, (poll_id, &token_id))?
+                        .map(|nft_ballot| nft_ballot.ballot)
+                }
+                None => ballots().may_load(deps.storage, (validated_address, poll_id))?,
+            };
+            let spent = ballot.map(|ballot| quadratic_cost(&ballot.options)).unwrap_or_default();
+            Some(credits.saturating_sub(spent))
+        }
+        _ => None,
+    };
+    to_binary(&CreditsResponse { remaining })
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::WrongContract { contract: stored.contract });
+    }
+    let stored_version = parse_version(&stored.version)?;
+    if stored_version > parse_version(CONTRACT_VERSION)? {
+        return Err(ContractError::CannotDowngrade {
+            stored: stored.version,
+            current: CONTRACT_VERSION.to_string(),
+        });
+    }
+
+    // Run every migration newer than the stored version, oldest first
+    for (version, migration) in MIGRATIONS {
+        if stored_version < parse_version(version)? {
+            migration(deps.storage, &env)?;
+        }
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
+fn parse_version(version: &str) -> Result<Version, ContractError> {
+    Version::parse(version).map_err(|_| ContractError::InvalidVersion { version: version.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::contract::{execute, instantiate, migrate, query}; // the contract instantiate function
+    use crate::ContractError;
+    use crate::helpers::{allowlist_leaf, allowlist_node, commitment};
+    use crate::hooks::{HookExecuteMsg, PollHookMsg, VoteHookMsg};
+    use crate::msg::{
+        AdminInfo, AdminsResponse, AllPollsResponse, AllowlistProof, BallotsResponse, ConfigResponse,
+        CreditsResponse, Cw4QueryMsg, Cw721QueryMsg, DelegationChainResponse, EscrowResponse,
+        ExecuteMsg, ExternalRefResponse, HooksResponse, InstantiateMsg, MemberResponse, MigrateMsg,
+        NftBallotsResponse, NftVoteResponse, OwnerOfResponse, OwnershipResponse, PollResponse,
+        QueryMsg, ReceiveMsg, RoleHoldersResponse, RunoffResponse, TotalWeightResponse, TreasuryResponse, VoteResponse,
+        VoterBallotsResponse,
+    };
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+    }; // mock functions to mock an environment, message info, dependencies
+    use cosmwasm_std::{
+        attr, from_binary, to_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Decimal, Event,
+        ContractResult, FullDelegation, OwnedDeps, SubMsg, SystemError, SystemResult, Uint128,
+        Validator, WasmMsg, WasmQuery,
+    };
+    use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+    use crate::migrations::v0_1_0;
+    use crate::state::{
+        Ballot, DelegationScope, DepositConfig, Fees, Outcome, PollResult, PollStatus, PollType, Role,
+        Threshold, Veto, CONFIG,
+    };
+    use cw2::{get_contract_version, set_contract_version};
+    use cw_utils::{Expiration, PaymentError};
+    use sha2::{Digest, Sha256};
+
+    // Two fake addresses we will use to mock_info
+    pub const ADDR1: &str = "addr1";
+    pub const ADDR2: &str = "addr2";
+
+    pub const LFG: &str = "addr3";
+
+    pub const VALIDATOR: &str = "kujiravaloper1546l88y0g9ch5v25dg4lmfewgelsd3v966qj3y";
+    pub const OTHER_VALIDATOR: &str = "kujiravaloper1other";
+
+    // Delegates each amount of ukuji to VALIDATOR in the mock querier
+    fn mock_stake(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, stakes: &[(&str, u128)]) {
+        let delegations: Vec<(&str, &str, u128)> = stakes
+            .iter()
+            .map(|(voter, amount)| (*voter, VALIDATOR, *amount))
+            .collect();
+        mock_delegations(deps, &delegations)
+    }
+
+    fn mock_delegations(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        delegations: &[(&str, &str, u128)],
+    ) {
+        let validators: Vec<Validator> = [VALIDATOR, OTHER_VALIDATOR]
+            .iter()
+            .map(|address| Validator {
+                address: address.to_string(),
+                commission: Decimal::percent(5),
+                max_commission: Decimal::percent(10),
+                max_change_rate: Decimal::percent(1),
+            })
+            .collect();
+        let delegations: Vec<FullDelegation> = delegations
+            .iter()
+            .map(|(delegator, validator, amount)| FullDelegation {
+                delegator: Addr::unchecked(*delegator),
+                validator: validator.to_string(),
+                amount: Coin::new(*amount, "ukuji"),
+                can_redelegate: Coin::new(*amount, "ukuji"),
+                accumulated_rewards: vec![],
+            })
+            .collect();
+        deps.querier.update_staking("ukuji", &validators, &delegations);
+    }
+
+    // Answers cw4 Member and TotalWeight queries for a group
+    // Each member has a weight from the height they joined
+    fn mock_group(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        members: &[(&str, u64, u64)],
+    ) {
+        let members: Vec<(String, u64, u64)> = members
+            .iter()
+            .map(|(member, weight, joined)| (member.to_string(), *weight, *joined))
+            .collect();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { msg, .. } => {
+                let res = match from_binary(msg).unwrap() {
+                    Cw4QueryMsg::Member { addr, at_height } => {
+                        let weight = members
+                            .iter()
+                            .find(|m| m.0 == addr && at_height.map_or(true, |h| h >= m.2))
+                            .map(|member| member.1);
+                        to_binary(&MemberResponse { weight })
+                    }
+                    Cw4QueryMsg::TotalWeight { at_height } => {
+                        let weight = members
+                            .iter()
+                            .filter(|m| at_height.map_or(true, |h| h >= m.2))
+                            .map(|member| member.1)
+                            .sum();
+                        to_binary(&TotalWeightResponse { weight })
+                    }
+                };
+                SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest { kind: "wasm".to_string() }),
+        });
+    }
+
+    #[test]
+    fn test_instantiate() {
+        // Mock the dependencies, must be mutable so we can pass it as a mutable, empty vector means our contract has no balance
+        let mut deps = mock_dependencies();
+        // Mock the contract environment, contains the block info, contract address, etc.
+        let env = mock_env();
+        // Mock the message info, ADDR1 will be the sender, the empty vec means we sent no funds.
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+
+        // Create a message where we (the sender) will be an admin
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        // Call instantiate, unwrap to assert success
+        let res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        
+
+        assert_eq!(
+            res.attributes,
+            vec![attr("action", "instantiate"), attr("admin", ADDR1)]
+        )
+    }
+    #[test]
+    fn test_instantiate_with_admin() {
+        // Mock the dependencies, must be mutable so we can pass it as a mutable, empty vector means our contract has no balance
+        let mut deps = mock_dependencies();
+        // Mock the contract environment, contains the block info, contract address, etc.
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Create a message where ADDR2 will be the admin, not the sender (ADDR1)
+        let msg = InstantiateMsg {
+            admin: Some(ADDR2.to_string()),
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        assert_eq!(
+            res.attributes,
+            vec![attr("action", "instantiate"), attr("admin", ADDR2)],
+        )
+    }
+    #[test]
+    fn test_execute_create_poll_valid() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // New execute msg
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec![
+                "Cosmos Hub".to_string(),
+                "Juno".to_string(),
+                "Osmosis".to_string(),
+            ],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+
+        // Unwrap to assert success
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        assert_eq!(
+            _res.attributes,
+            vec![attr("action", "create poll"), attr("poll_id", "1")],
+        );
+        assert_eq!(_res.data, Some(to_binary(&1u64).unwrap()));
+
+        // The next poll gets the next id and can be looked up by its external reference
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: Some("proposal-42".to_string()),
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+        assert_eq!(_res.data, Some(to_binary(&2u64).unwrap()));
+
+        let query_msg = QueryMsg::ExternalRef { external_ref: "proposal-42".to_string() };
+        let bin = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let res: ExternalRefResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.poll_id, Some(2));
+
+        // The same external reference can't be used twice
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::DuplicateExternalRef { .. }));
+    }
+
+    #[test]
+    fn test_check_poll_closed(){
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // New execute msg
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec![
+                "Cosmos Hub".to_string(),
+                "Juno".to_string(),
+                "Osmosis".to_string(),
+            ],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+
+        // Unwrap to assert success
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        
+        let msg = ExecuteMsg::ClosePoll { poll_id: 1 };
+
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        println!("Output for ClosePoll message: {res:?}");
+
+
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Osmosis".to_string()],
+            proof: None,
+        };
+
+        let res = execute(deps.as_mut(), env, info, msg);
+        
+        println!("Output for voting on a closed poll: {res:?}");
+
+    }
+
+    #[test]
     fn test_execute_create_poll_invalid() {
         let mut deps = mock_dependencies();
         let env = mock_env();
         let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
         // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite number?".to_string(),
+            options: vec![
+                "1".to_string(),
+                "2".to_string(),
+                "3".to_string(),
+                "4".to_string(),
+                "5".to_string(),
+                "6".to_string(),
+                "7".to_string(),
+                "8".to_string(),
+                "9".to_string(),
+                "10".to_string(),
+                "11".to_string(),
+            ],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        // Unwrap error to assert failure
+        let _err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+
+        // Every vote counts towards the votes cast, so that quorum could never be missed
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: Some(Threshold::VotesCast { percentage: Decimal::percent(50) }),
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidQuorum {}));
+    }
+
+    #[test]
+    fn test_execute_vote_valid() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create the poll
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec![
+                "Cosmos Hub".to_string(),
+                "Juno".to_string(),
+                "Osmosis".to_string(),
+            ],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create the vote, first time voting
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Osmosis".to_string()],
+            proof: None,
+        };
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+        assert_eq!(
+            res.attributes,
+            vec![attr("action", "vote on poll"), attr("on:", "Osmosis")],
+        );
+    }
+
+    #[test]
+    fn test_events() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: Some("proposal-42".to_string()),
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("poll_created")
+                .add_attribute("poll_id", "1")
+                .add_attribute("creator", ADDR1)
+                .add_attribute("question", "What's your favourite Cosmos coin?")
+                .add_attribute("options", r#"["Juno","Osmosis"]"#)
+                .add_attribute("poll_type", r#""plurality""#)
+                .add_attribute("external_ref", "proposal-42")]
+        );
+
+        let msg = ExecuteMsg::Vote { poll_id: 1, vote: vec!["Juno".to_string()], proof: None };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("vote_cast")
+                .add_attribute("poll_id", "1")
+                .add_attribute("voter", ADDR1)
+                .add_attribute("options", r#"["Juno"]"#)
+                .add_attribute("weight", "1")
+                .add_attribute("tallies", r#"[["Juno","1"],["Osmosis","0"]]"#)
+                .add_attribute("turnout", "1")]
+        );
+
+        // Voting again changes the vote
+        let msg = ExecuteMsg::Vote { poll_id: 1, vote: vec!["Osmosis".to_string()], proof: None };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("vote_changed")
+                .add_attribute("poll_id", "1")
+                .add_attribute("voter", ADDR1)
+                .add_attribute("previous_options", r#"["Juno"]"#)
+                .add_attribute("previous_weight", "1")
+                .add_attribute("options", r#"["Osmosis"]"#)
+                .add_attribute("weight", "1")
+                .add_attribute("tallies", r#"[["Juno","0"],["Osmosis","1"]]"#)
+                .add_attribute("turnout", "1")]
+        );
+
+        let msg = ExecuteMsg::ClosePoll { poll_id: 1 };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("poll_closed")
+                .add_attribute("poll_id", "1")
+                .add_attribute("outcome", "passed")
+                .add_attribute("winner", "Osmosis")
+                .add_attribute("tallies", r#"[["Juno","0"],["Osmosis","1"]]"#)
+                .add_attribute("turnout", "1")]
+        );
+
+        // add_admin reports the role it grants
+        let msg = ExecuteMsg::AddAdmin { addmin: ADDR2.to_string() };
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("admin_changed")
+                .add_attribute("address", ADDR2)
+                .add_attribute("role", "poll_creator")
+                .add_attribute("change", "granted")]
+        );
+    }
+
+    #[test]
+    fn test_execute_vote_ranked_choice() {
+        let mut deps = mock_dependencies();
+        let voters = [ADDR1, "voter1", "voter2", "voter3", "voter4", "voter5", "voter6"];
+        let stakes: Vec<(&str, u128)> = voters.iter().map(|voter| (*voter, 1)).collect();
+        mock_stake(&mut deps, &stakes);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create a ranked choice poll
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "Who should chair the council?".to_string(),
+            options: vec![
+                "Alice".to_string(),
+                "Bob".to_string(),
+                "Carol".to_string(),
+            ],
+            poll_type: PollType::RankedChoice,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // A ranking with the same option twice is rejected
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Alice".to_string(), "Alice".to_string()],
+            proof: None,
+        };
+        let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidBallot {}));
+
+        // Every candidate has two first preferences, Carol's voters prefer Bob
+        let ballots = vec![
+            ("voter1", vec!["Alice", "Bob"]),
+            ("voter2", vec!["Alice"]),
+            ("voter3", vec!["Bob", "Alice"]),
+            ("voter4", vec!["Carol", "Bob"]),
+            ("voter5", vec!["Carol", "Bob"]),
+            ("voter6", vec!["Bob"]),
+        ];
+        for (voter, ranking) in ballots {
+            let msg = ExecuteMsg::Vote {
+                poll_id: 1,
+                vote: ranking.into_iter().map(String::from).collect(),
+                proof: None,
+            };
+            let info = mock_info(voter, &[Coin::new(1, "ukuji")]);
+            let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+
+        // No runoff before the poll is closed
+        let msg = QueryMsg::Runoff { poll_id: 1 };
+        let bin = query(deps.as_ref(), env.clone(), msg.clone()).unwrap();
+        let res: RunoffResponse = from_binary(&bin).unwrap();
+        assert!(res.runoff.is_none());
+
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        let close = ExecuteMsg::ClosePoll { poll_id: 1 };
+        let _res = execute(deps.as_mut(), env.clone(), info, close).unwrap();
+
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let runoff = from_binary::<RunoffResponse>(&bin).unwrap().runoff.unwrap();
+        // Carol is eliminated on the tie-break and both her ballots move to Bob
+        assert_eq!(runoff.rounds.len(), 2);
+        assert_eq!(runoff.rounds[0].eliminated, Some("Carol".to_string()));
+        assert_eq!(
+            runoff.rounds[1].tallies,
+            vec![
+                ("Alice".to_string(), Uint128::new(2)),
+                ("Bob".to_string(), Uint128::new(4)),
+            ]
+        );
+        assert_eq!(runoff.winner, Some("Bob".to_string()));
+    }
+
+    #[test]
+    fn test_execute_vote_approval() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 100)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create an approval poll allowing two selections
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "Which features should we build next?".to_string(),
+            options: vec![
+                "Staking".to_string(),
+                "Governance".to_string(),
+                "Bridge".to_string(),
+            ],
+            poll_type: PollType::Approval { max_selections: 2 },
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Selecting all three options is over the limit
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Staking".to_string(), "Governance".to_string(), "Bridge".to_string()],
+            proof: None,
+        };
+        let _err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Staking".to_string(), "Governance".to_string()],
+            proof: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Revoting takes back both earlier approvals
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Governance".to_string(), "Bridge".to_string()],
+            proof: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let msg = QueryMsg::Poll { poll_id: 1 };
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.poll.unwrap().options,
+            vec![
+                ("Staking".to_string(), Uint128::zero()),
+                ("Governance".to_string(), Uint128::new(100)),
+                ("Bridge".to_string(), Uint128::new(100)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_execute_vote_quadratic() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create a quadratic poll with a budget of 10 credits
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "How should the community pool be spent?".to_string(),
+            options: vec!["Grants".to_string(), "Marketing".to_string()],
+            poll_type: PollType::Quadratic { credits: 10 },
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // 4 votes on one option cost 16 credits
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Grants".to_string(); 4],
+            proof: None,
+        };
+        let _err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+
+        // 3 votes for 9 credits plus 1 vote for 1 credit
+        let mut vote = vec!["Grants".to_string(); 3];
+        vote.push("Marketing".to_string());
+        let msg = ExecuteMsg::Vote { poll_id: 1, vote, proof: None };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let msg = QueryMsg::Credits {
+            poll_id: 1,
+            address: ADDR1.to_string(),
+            token_id: None,
+        };
+        let bin = query(deps.as_ref(), env.clone(), msg.clone()).unwrap();
+        let res: CreditsResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.remaining, Some(0));
+
+        // Revoting refunds the earlier allocation
+        let msg_vote = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Marketing".to_string(); 2],
+            proof: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg_vote).unwrap();
+
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: CreditsResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.remaining, Some(6));
+
+        let msg = QueryMsg::Poll { poll_id: 1 };
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.poll.unwrap().options,
+            vec![
+                ("Grants".to_string(), Uint128::zero()),
+                ("Marketing".to_string(), Uint128::new(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_execute_vote_stake_weighted() {
+        let mut deps = mock_dependencies();
+        // ADDR1 splits their stake, ADDR2 only delegates to a validator outside the set
+        mock_delegations(
+            &mut deps,
+            &[(ADDR1, VALIDATOR, 30), (ADDR1, OTHER_VALIDATOR, 20), (ADDR2, OTHER_VALIDATOR, 50)],
+        );
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract, only counting stake with VALIDATOR
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: Some(vec![VALIDATOR.to_string()]),
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Juno".to_string()],
+            proof: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap();
+
+        // ADDR2 has no stake with the configured validators
+        let info = mock_info(ADDR2, &[Coin::new(1, "ukuji")]);
+        let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotStaked {}));
+
+        let msg = QueryMsg::Poll { poll_id: 1 };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.poll.unwrap().options[0].1, Uint128::new(30));
+
+        let msg = QueryMsg::Vote {
+            poll_id: 1,
+            address: ADDR1.to_string(),
+        };
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: VoteResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.vote.unwrap().weight, Uint128::new(30));
+    }
+
+    #[test]
+    fn test_execute_vote_delegated() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 6), (ADDR2, 4), (LFG, 1)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What should the treasury fund?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: Some("treasury".to_string()),
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // ADDR2 delegates everything to ADDR1, LFG delegates treasury polls to ADDR2
+        let msg = ExecuteMsg::Delegate { to: ADDR1.to_string(), scope: DelegationScope::Global };
+        let addr2 = mock_info(ADDR2, &[Coin::new(1, "ukuji")]);
+        let res = execute(deps.as_mut(), env.clone(), addr2.clone(), msg).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("delegated")
+                .add_attribute("delegator", ADDR2)
+                .add_attribute("delegate", ADDR1)
+                .add_attribute("scope", "global")]
+        );
+        let msg = ExecuteMsg::Delegate {
+            to: ADDR2.to_string(),
+            scope: DelegationScope::Category("treasury".to_string()),
+        };
+        let lfg = mock_info(LFG, &[Coin::new(1, "ukuji")]);
+        let _res = execute(deps.as_mut(), env.clone(), lfg, msg).unwrap();
+
+        let msg = QueryMsg::DelegationChain {
+            address: LFG.to_string(),
+            category: Some("treasury".to_string()),
+        };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: DelegationChainResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.chain, vec![Addr::unchecked(ADDR2), Addr::unchecked(ADDR1)]);
+
+        // ADDR1 votes with everyone's power
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Juno".to_string()],
+            proof: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let query_msg = QueryMsg::Poll { poll_id: 1 };
+        let bin = query(deps.as_ref(), env.clone(), query_msg.clone()).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.poll.unwrap().options[0].1, Uint128::new(11));
+
+        // ADDR2 overrides and takes LFG's power along
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Osmosis".to_string()],
+            proof: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), addr2, msg).unwrap();
+        // ADDR1's ballot shrinks once per delegator taken off it
+        let events: Vec<_> = res.events.iter().map(|event| event.ty.as_str()).collect();
+        assert_eq!(events, vec!["vote_changed", "vote_changed", "vote_cast"]);
+        let attr = |event: &Event, key: &str| {
+            event.attributes.iter().find(|attr| attr.key == key).unwrap().value.clone()
+        };
+        assert_eq!(attr(&res.events[0], "voter"), ADDR1);
+        assert_eq!(attr(&res.events[0], "previous_weight"), "11");
+        assert_eq!(attr(&res.events[1], "voter"), ADDR1);
+        assert_eq!(attr(&res.events[1], "weight"), "6");
+        assert_eq!(attr(&res.events[2], "voter"), ADDR2);
+        assert_eq!(attr(&res.events[2], "weight"), "5");
+        let bin = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        let poll = res.poll.unwrap();
+        assert_eq!(
+            poll.options,
+            vec![("Juno".to_string(), Uint128::new(6)), ("Osmosis".to_string(), Uint128::new(5))]
+        );
+        assert_eq!(poll.turnout, Uint128::new(11));
+
+        // Power can't flow back round to ADDR1
+        let msg = ExecuteMsg::Delegate { to: LFG.to_string(), scope: DelegationScope::Global };
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::DelegationCycle {}));
+    }
+
+    #[test]
+    fn test_execute_vote_time_bounded() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
+        let mut env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Voting opens in 10 blocks and runs for 10 blocks
+        let height = env.block.height;
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: Some(Expiration::AtHeight(height + 10)),
+            end: Some(Expiration::AtHeight(height + 20)),
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let vote = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Juno".to_string()],
+            proof: None,
+        };
+        let query_msg = QueryMsg::Poll { poll_id: 1 };
+
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), vote.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::PollNotStarted {}));
+        let bin = query(deps.as_ref(), env.clone(), query_msg.clone()).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.status, Some(PollStatus::Pending));
+
+        env.block.height = height + 10;
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), vote.clone()).unwrap();
+        let bin = query(deps.as_ref(), env.clone(), query_msg.clone()).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.status, Some(PollStatus::Open));
+
+        // The poll ends by itself without a close transaction
+        env.block.height = height + 20;
+        let err = execute(deps.as_mut(), env.clone(), info, vote).unwrap_err();
+        assert!(matches!(err, ContractError::ClosedPoll {}));
+        let bin = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.status, Some(PollStatus::Ended));
+
+        let msg = QueryMsg::AllPolls {
+            start_after: None,
+            limit: None,
+            closed: None,
+            creator: None,
+            created_after: None,
+            created_before: None,
+        };
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: AllPollsResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.polls[0].status, PollStatus::Ended);
+    }
+
+    #[test]
+    fn test_execute_vote_commit_reveal() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 6), (ADDR2, 4)]);
+        let mut env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Ballots are committed for 10 blocks and revealed over the next 10
+        let height = env.block.height;
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: Some(Expiration::AtHeight(height + 10)),
+            reveal_end: Some(Expiration::AtHeight(height + 20)),
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // ADDR2 copies ADDR1's commitment to vote the same way
+        let juno = vec!["Juno".to_string()];
+        let addr1_commitment = commitment(1, &Addr::unchecked(ADDR1), &juno, "salt1").unwrap();
+        for voter in [ADDR1, ADDR2] {
+            let msg = ExecuteMsg::Commit { poll_id: 1, commitment: addr1_commitment.clone() };
+            let info = mock_info(voter, &[Coin::new(1, "ukuji")]);
+            let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+
+        // Only ADDR1 reveals, ADDR2's commitment stays unrevealed
+        // Revealing is free, so it takes no funds
+        env.block.height = height + 10;
+        let msg = ExecuteMsg::Reveal {
+            poll_id: 1,
+            vote: juno.clone(),
+            salt: "salt1".to_string(),
+        };
+        let _res = execute(deps.as_mut(), env.clone(), mock_info(ADDR1, &[]), msg.clone()).unwrap();
+        // The copied commitment is bound to ADDR1, so ADDR2 can't reveal with it
+        let err = execute(deps.as_mut(), env.clone(), mock_info(ADDR2, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidReveal {}));
+
+        let query_msg = QueryMsg::Poll { poll_id: 1 };
+        let bin = query(deps.as_ref(), env.clone(), query_msg.clone()).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.status, Some(PollStatus::Revealing));
+        assert_eq!(res.poll.unwrap().options[0].1, Uint128::zero());
+
+        // Tallies show up once the reveal phase is over
+        env.block.height = height + 20;
+        let bin = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        let poll = res.poll.unwrap();
+        assert_eq!(poll.options[0].1, Uint128::new(6));
+        assert_eq!(poll.unrevealed, Uint128::new(4));
+
+        let msg = ExecuteMsg::Reveal {
+            poll_id: 1,
+            vote: juno,
+            salt: "wrong".to_string(),
+        };
+        let info = mock_info(ADDR2, &[]);
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotRevealing {}));
+    }
+
+    #[test]
+    fn test_close_poll_result() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 6), (ADDR2, 4)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Each poll needs a turnout of 10 and 60% of the votes cast for the winner
+        // Poll 1 meets the quorum, poll 2 misses it
+        for (poll_id, quorum) in [(1, 10), (2, 11)] {
+            let msg = ExecuteMsg::CreatePoll {
+                external_ref: None,
+                question: "What's your favourite Cosmos coin?".to_string(),
+                options: vec!["Juno".to_string(), "Osmosis".to_string()],
+                poll_type: PollType::Plurality,
+                category: None,
+                start: None,
+                end: None,
+                reveal_end: None,
+                quorum: Some(Threshold::AbsoluteCount { weight: Uint128::new(quorum) }),
+                threshold: Some(Threshold::VotesCast { percentage: Decimal::percent(60) }),
+                token: None,
+                nft: None,
+                merkle_root: None,
+                msgs: vec![],
+            };
+            let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+            for (voter, vote) in [(ADDR1, "Juno"), (ADDR2, "Osmosis")] {
+                let msg = ExecuteMsg::Vote {
+                    poll_id,
+                    vote: vec![vote.to_string()],
+                    proof: None,
+                };
+                let info = mock_info(voter, &[Coin::new(1, "ukuji")]);
+                let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+            }
+
+            let msg = ExecuteMsg::ClosePoll { poll_id };
+            let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+            // A closed poll keeps its result
+            let err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+            assert!(matches!(err, ContractError::ClosedPoll {}));
+        }
+
+        let msg = QueryMsg::Poll { poll_id: 1 };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.poll.unwrap().result,
+            Some(PollResult { outcome: Outcome::Passed, winner: Some("Juno".to_string()) })
+        );
+
+        let msg = QueryMsg::Poll { poll_id: 2 };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.poll.unwrap().result,
+            Some(PollResult { outcome: Outcome::NoQuorum, winner: None })
+        );
+
+        // Ties go to the option listed first, which misses a 60% threshold
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Approval { max_selections: 2 },
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: Some(Threshold::EligiblePower {
+                percentage: Decimal::percent(60),
+                eligible: Uint128::new(20),
+            }),
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Vote {
+            poll_id: 3,
+            vote: vec!["Osmosis".to_string(), "Juno".to_string()],
+            proof: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::ClosePoll { poll_id: 3 };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let msg = QueryMsg::Poll { poll_id: 3 };
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.poll.unwrap().result,
+            Some(PollResult { outcome: Outcome::Rejected, winner: Some("Juno".to_string()) })
+        );
+    }
+
+    #[test]
+    fn test_execute_passed_poll() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let payout: CosmosMsg = BankMsg::Send {
+            to_address: ADDR2.to_string(),
+            amount: vec![Coin::new(5, "ukuji")],
+        }
+        .into();
+
+        // Messages need a yes/no poll
+        let mut msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "Should we pay addr2 for their work?".to_string(),
+            options: vec!["Yes".to_string(), "No".to_string(), "Abstain".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![payout.clone()],
+        };
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NotExecutable {}));
+
+        // And a quorum and threshold the yes votes have to reach
+        if let ExecuteMsg::CreatePoll { options, .. } = &mut msg {
+            options.pop();
+        }
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NeedsThreshold {}));
+
+        if let ExecuteMsg::CreatePoll { quorum, threshold, .. } = &mut msg {
+            *quorum = Some(Threshold::AbsoluteCount { weight: Uint128::new(1) });
+            *threshold = Some(Threshold::VotesCast { percentage: Decimal::percent(50) });
+        }
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Yes".to_string()],
+            proof: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Nothing runs until the poll is closed
+        let execute_msg = ExecuteMsg::Execute { poll_id: 1 };
+        let err =
+            execute(deps.as_mut(), env.clone(), info.clone(), execute_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NotPassed {}));
+
+        let msg = ExecuteMsg::ClosePoll { poll_id: 1 };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // The payout comes out of the treasury, on top of the fee paid to execute
+        let treasury = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            let bin = query(deps.as_ref(), mock_env(), QueryMsg::Treasury {}).unwrap();
+            from_binary::<TreasuryResponse>(&bin).unwrap().balances[0].amount
+        };
+        let before = treasury(&deps);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), execute_msg.clone()).unwrap();
+        assert_eq!(res.messages, vec![SubMsg::new(payout)]);
+        assert_eq!(treasury(&deps), before + Uint128::new(1) - Uint128::new(5));
+
+        // The messages only run once
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), execute_msg).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyExecuted {}));
+
+        // Messages can't touch tokens escrowed by token weighted polls
+        let mut msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: Some("token".to_string()),
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+        let transfer = Cw20ExecuteMsg::Transfer { recipient: ADDR2.to_string(), amount: 1u128.into() };
+        if let ExecuteMsg::CreatePoll { token, quorum, threshold, msgs, .. } = &mut msg {
+            *token = None;
+            *quorum = Some(Threshold::AbsoluteCount { weight: Uint128::new(1) });
+            *threshold = Some(Threshold::VotesCast { percentage: Decimal::percent(50) });
+            *msgs = vec![WasmMsg::Execute {
+                contract_addr: "token".to_string(),
+                msg: to_binary(&transfer).unwrap(),
+                funds: vec![],
+            }
+            .into()];
+        }
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::EscrowedToken {}));
+    }
+
+    // #[test]
+    // fn test_execute_vote_invalid() {
+    //     let mut deps = mock_dependencies();
+    //     let env = mock_env();
+    //     let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+    //     // Instantiate the contract
+    //     let msg = InstantiateMsg { admin: None, validators: None, fees: None, deposit: None };
+    //     let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+    //     // Create the vote, some_id poll is not created yet.
+    //     let msg = ExecuteMsg::Vote {
+    //         poll_id: 1,
+    //         vote: "Juno".to_string(),
+    //         proof: None,
+    //     };
+    //     // Unwrap to assert error
+    //     let _err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+
+    //     // Create the poll
+    //     let msg = ExecuteMsg::CreatePoll {
+    //         poll_id: 1,
+    //         question: "What's your favourite Cosmos coin?".to_string(),
+    //         options: vec![
+    //             "Cosmos Hub".to_string(),
+    //             "Juno".to_string(),
+    //             "Osmosis".to_string(),
+    //         ],
+    //     };
+    //     let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+    //     // Vote on a now existing poll but the option "DVPN" does not exist
+    //     let msg = ExecuteMsg::Vote {
+    //         poll_id: 1,
+    //         vote: "DVPN".to_string(),
+    //         proof: None,
+    //     };
+
+    //     let _err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+    // }
+
+    #[test]
+    fn test_query_all_polls() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create a poll
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec![
+                "Cosmos Hub".to_string(),
+                "Juno".to_string(),
+                "Osmosis".to_string(),
+            ],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create a second poll
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your colour?".to_string(),
+            options: vec!["Red".to_string(), "Green".to_string(), "Blue".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let msg = QueryMsg::AllPolls {
+            start_after: None,
+            limit: None,
+            closed: None,
+            creator: None,
+            created_after: None,
+            created_before: None,
+        };
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: AllPollsResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.polls.len(), 2);
+    }
+
+    #[test]
+    fn test_query_all_polls_with_no_polls_created() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let msg = QueryMsg::AllPolls {
+            start_after: None,
+            limit: None,
+            closed: None,
+            creator: None,
+            created_after: None,
+            created_before: None,
+        };
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: AllPollsResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.polls.len(), 0);
+    }
+
+    #[test]
+    fn test_query_all_polls_filtered() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::GrantRole { address: ADDR2.to_string(), role: Role::PollCreator };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // ADDR1 creates the first two polls, ADDR2 creates the third a minute later
+        let created = env.block.time;
+        for creator in [ADDR1, ADDR1, ADDR2] {
+            if creator == ADDR2 {
+                env.block.time = created.plus_seconds(60);
+            }
+            let msg = ExecuteMsg::CreatePoll {
+                external_ref: None,
+                question: "What's your favourite Cosmos coin?".to_string(),
+                options: vec!["Juno".to_string(), "Osmosis".to_string()],
+                poll_type: PollType::Plurality,
+                category: None,
+                start: None,
+                end: None,
+                reveal_end: None,
+                quorum: None,
+                threshold: None,
+                token: None,
+                nft: None,
+                merkle_root: None,
+                msgs: vec![],
+            };
+            let info = mock_info(creator, &[Coin::new(1, "ukuji")]);
+            let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+        let msg = ExecuteMsg::ClosePoll { poll_id: 1 };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let list = |start_after, limit, closed, creator: Option<&str>, created_after| {
+            let msg = QueryMsg::AllPolls {
+                start_after,
+                limit,
+                closed,
+                creator: creator.map(|creator| creator.to_string()),
+                created_after,
+                created_before: None,
+            };
+            let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+            let res: AllPollsResponse = from_binary(&bin).unwrap();
+            res.polls.into_iter().map(|poll| poll.poll_id).collect::<Vec<_>>()
+        };
+
+        assert_eq!(list(None, Some(2), None, None, None), vec![1, 2]);
+        assert_eq!(list(None, Some(0), None, None, None), vec![1]);
+        assert_eq!(list(Some(2), Some(2), None, None, None), vec![3]);
+        assert_eq!(list(None, None, None, Some(ADDR2), None), vec![3]);
+        assert_eq!(list(None, None, Some(true), None, None), vec![1]);
+        assert_eq!(list(Some(1), None, Some(false), Some(ADDR1), None), vec![2]);
+        assert_eq!(list(None, None, None, None, Some(created)), vec![3]);
+
+        // A filter matching nothing stops after 100 polls and says where to carry on
+        for _ in 0..100 {
+            let msg = ExecuteMsg::CreatePoll {
+                external_ref: None,
+                question: "What's your favourite Cosmos coin?".to_string(),
+                options: vec!["Juno".to_string(), "Osmosis".to_string()],
+                poll_type: PollType::Plurality,
+                category: None,
+                start: None,
+                end: None,
+                reveal_end: None,
+                quorum: None,
+                threshold: None,
+                token: None,
+                nft: None,
+                merkle_root: None,
+                msgs: vec![],
+            };
+            let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+            let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+        let page = |start_after| {
+            let msg = QueryMsg::AllPolls {
+                start_after,
+                limit: None,
+                closed: None,
+                creator: None,
+                created_after: Some(env.block.time),
+                created_before: None,
+            };
+            let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+            from_binary::<AllPollsResponse>(&bin).unwrap()
+        };
+        let res = page(None);
+        assert!(res.polls.is_empty());
+        assert_eq!(res.next_start_after, Some(100));
+        let res = page(Some(100));
+        assert!(res.polls.is_empty());
+        assert_eq!(res.next_start_after, None);
+    }
+
+    #[test]
+    fn test_query_poll() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create a poll
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec![
+                "Cosmos Hub".to_string(),
+                "Juno".to_string(),
+                "Osmosis".to_string(),
+            ],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // Query for the poll that exists
+        let msg = QueryMsg::Poll {
+            poll_id: 1,
+        };
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        // Expect a poll
+        assert!(res.poll.is_some());
+    }
+
+    #[test]
+    fn test_query_poll_fail() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create a poll
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec![
+                "Cosmos Hub".to_string(),
+                "Juno".to_string(),
+                "Osmosis".to_string(),
+            ],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // Query for the poll that exists
+        let msg = QueryMsg::Poll {
+            poll_id: 1,
+        };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        // Expect a poll
+        assert!(res.poll.is_some());
+
+        // Query for the poll that does not exists
+        let msg = QueryMsg::Poll {
+            poll_id: 2,
+        };
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert!(res.poll.is_none());
+    }
+
+    #[test]
+    fn test_query_vote() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create a poll
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec![
+                "Cosmos Hub".to_string(),
+                "Juno".to_string(),
+                "Osmosis".to_string(),
+            ],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create a vote
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Juno".to_string()],
+            proof: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let msg = QueryMsg::Vote {
+            poll_id: 1,
+            address: ADDR1.to_string(),
+        };
+
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: VoteResponse = from_binary(&bin).unwrap();
+        assert!(res.vote.is_some());
+    }
+
+    #[test]
+    fn test_query_ballots() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1), (ADDR2, 2), (LFG, 3)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        for _ in 0..2 {
+            let msg = ExecuteMsg::CreatePoll {
+                external_ref: None,
+                question: "What's your favourite Cosmos coin?".to_string(),
+                options: vec!["Juno".to_string(), "Osmosis".to_string()],
+                poll_type: PollType::Plurality,
+                category: None,
+                start: None,
+                end: None,
+                reveal_end: None,
+                quorum: None,
+                threshold: None,
+                token: None,
+                nft: None,
+                merkle_root: None,
+                msgs: vec![],
+            };
+            let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+
+        // Everyone votes on the first poll, only ADDR1 votes on the second
+        for (voter, poll_id) in [(ADDR1, 1), (ADDR2, 1), (LFG, 1), (ADDR1, 2)] {
+            let msg = ExecuteMsg::Vote {
+                poll_id,
+                vote: vec!["Juno".to_string()],
+                proof: None,
+            };
+            let info = mock_info(voter, &[Coin::new(1, "ukuji")]);
+            let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+
+        let list = |poll_id, start_after: Option<&str>, limit| {
+            let msg = QueryMsg::Ballots {
+                poll_id,
+                start_after: start_after.map(|address| address.to_string()),
+                limit,
+            };
+            let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+            let res: BallotsResponse = from_binary(&bin).unwrap();
+            res.ballots
+                .into_iter()
+                .map(|(address, ballot)| (address.to_string(), ballot.weight.u128()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            list(1, None, Some(2)),
+            vec![(ADDR1.to_string(), 1), (ADDR2.to_string(), 2)]
+        );
+        assert_eq!(list(1, Some(ADDR2), Some(2)), vec![(LFG.to_string(), 3)]);
+        assert_eq!(list(2, None, None), vec![(ADDR1.to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_query_voter_ballots() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // ADDR1 votes on the first and third polls, then closes the third
+        for (poll_id, vote) in [(1, true), (2, false), (3, true)] {
+            let msg = ExecuteMsg::CreatePoll {
+                external_ref: None,
+                question: "What's your favourite Cosmos coin?".to_string(),
+                options: vec!["Juno".to_string(), "Osmosis".to_string()],
+                poll_type: PollType::Plurality,
+                category: None,
+                start: None,
+                end: None,
+                reveal_end: None,
+                quorum: None,
+                threshold: None,
+                token: None,
+                nft: None,
+                merkle_root: None,
+                msgs: vec![],
+            };
+            let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+            if vote {
+                let msg = ExecuteMsg::Vote {
+                    poll_id,
+                    vote: vec!["Osmosis".to_string()],
+                    proof: None,
+                };
+                let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+            }
+        }
+        let msg = ExecuteMsg::ClosePoll { poll_id: 3 };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let msg = QueryMsg::VoterBallots {
+            address: ADDR1.to_string(),
+            start_after: None,
+            limit: Some(1),
+        };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: VoterBallotsResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.ballots.len(), 1);
+        assert_eq!(res.ballots[0].poll_id, 1);
+        assert_eq!(res.ballots[0].ballot.options, vec!["Osmosis".to_string()]);
+        assert_eq!(res.ballots[0].status, PollStatus::Open);
+
+        let msg = QueryMsg::VoterBallots {
+            address: ADDR1.to_string(),
+            start_after: Some(1),
+            limit: None,
+        };
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: VoterBallotsResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.ballots.len(), 1);
+        assert_eq!(res.ballots[0].poll_id, 3);
+        assert_eq!(res.ballots[0].status, PollStatus::Closed);
+    }
+
+    // Previous code omitted
+    #[test]
+    fn test_query_vote_fail() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
         let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
+        // Create a poll
         let msg = ExecuteMsg::CreatePoll {
-            poll_id: "some_id".to_string(),
-            question: "What's your favourite number?".to_string(),
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
             options: vec![
-                "1".to_string(),
-                "2".to_string(),
-                "3".to_string(),
-                "4".to_string(),
-                "5".to_string(),
-                "6".to_string(),
-                "7".to_string(),
-                "8".to_string(),
-                "9".to_string(),
-                "10".to_string(),
-                "11".to_string(),
+                "Cosmos Hub".to_string(),
+                "Juno".to_string(),
+                "Osmosis".to_string(),
             ],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
         };
-        // Unwrap error to assert failure
-        let _err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Create a vote
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Juno".to_string()],
+            proof: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // Query for a vote that exists
+        let msg = QueryMsg::Vote {
+            poll_id: 1,
+            address: ADDR1.to_string(),
+        };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: VoteResponse = from_binary(&bin).unwrap();
+        // Expect the vote to exist
+        assert!(res.vote.is_some());
+
+        // Query for a vote that does not exists
+        let msg = QueryMsg::Vote {
+            poll_id: 1,
+            address: ADDR2.to_string(),
+        };
+
+        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let res: VoteResponse = from_binary(&bin).unwrap();
+
+        assert!(res.vote.is_none())
+    }
+
+    #[test]
+    fn test_fees() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[]);
+        // Polls cost 5uusdc to create, votes are free
+        let fees = Fees {
+            denom: "uusdc".to_string(),
+            create_poll: Uint128::new(5),
+            vote: Uint128::zero(),
+            close_poll: Uint128::new(1),
+            admin: Uint128::new(1),
+        };
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: Some(fees.clone()),
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let err = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Payment(PaymentError::NoFunds {})));
+
+        let info = mock_info(ADDR1, &[Coin::new(5, "ukuji")]);
+        let err = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Payment(PaymentError::MissingDenom(_))));
+
+        let info = mock_info(ADDR1, &[Coin::new(4, "uusdc")]);
+        let err = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFee { .. }));
+
+        let info = mock_info(ADDR1, &[Coin::new(5, "uusdc")]);
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec!["Juno".to_string()],
+            proof: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), mock_info(ADDR1, &[]), msg.clone()).unwrap();
+
+        // Funds sent with a free action are turned away rather than kept outside the treasury
+        let info = mock_info(ADDR1, &[Coin::new(1, "uusdc")]);
+        let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Payment(PaymentError::NonPayable {})));
+
+        // Only the first admin can change the fees
+        let new_fees = Fees { vote: Uint128::new(2), ..fees };
+        let msg = ExecuteMsg::UpdateConfig {
+            fees: Some(new_fees.clone()),
+            deposit: None,
+        };
+        let info = mock_info(ADDR2, &[Coin::new(1, "uusdc")]);
+        let err = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { .. }));
+
+        let info = mock_info(ADDR1, &[Coin::new(1, "uusdc")]);
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let bin = query(deps.as_ref(), env, QueryMsg::Config {}).unwrap();
+        let res: ConfigResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.config.fees, new_fees);
+    }
+
+    #[test]
+    fn test_withdraw_fees() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // Overpaying the 1ukuji fee still goes to the treasury
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let paid = mock_info(ADDR1, &[Coin::new(3, "ukuji")]);
+        let _res = execute(deps.as_mut(), env.clone(), paid, msg).unwrap();
+
+        // 3ukuji from the poll plus the 1ukuji fee for withdrawing
+        let msg = ExecuteMsg::WithdrawFees {
+            amount: Coin::new(2, "ukuji"),
+            recipient: ADDR2.to_string(),
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: ADDR2.to_string(),
+                amount: vec![Coin::new(2, "ukuji")],
+            })]
+        );
+
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Treasury {}).unwrap();
+        let res: TreasuryResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.balances, vec![Coin::new(2, "ukuji")]);
+
+        let msg = ExecuteMsg::WithdrawFees {
+            amount: Coin::new(10, "ukuji"),
+            recipient: ADDR2.to_string(),
+        };
+        let err = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientTreasury { .. }));
+
+        let info = mock_info(ADDR2, &[Coin::new(1, "ukuji")]);
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { .. }));
+    }
+
+    #[test]
+    fn test_poll_deposits() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 6), (ADDR2, 4)]);
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Anyone can create a poll by locking 10ukuji, a third of the turnout voting NoWithVeto vetoes it
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: Some(DepositConfig {
+                amount: Uint128::new(10),
+                open_creation: true,
+                burn: false,
+                veto: Some(Veto {
+                    option: "NoWithVeto".to_string(),
+                    threshold: Decimal::from_ratio(1u128, 3u128),
+                }),
+            }),
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let creator = mock_info(ADDR2, &[Coin::new(11, "ukuji")]);
+        // Poll 1 gets refunded, poll 2 gets vetoed
+        for (poll_id, vote) in [(1, "Yes"), (2, "NoWithVeto")] {
+            let msg = ExecuteMsg::CreatePoll {
+                external_ref: None,
+                question: "Should we upgrade?".to_string(),
+                options: vec!["Yes".to_string(), "No".to_string(), "NoWithVeto".to_string()],
+                poll_type: PollType::Plurality,
+                category: None,
+                start: None,
+                end: None,
+                reveal_end: None,
+                quorum: Some(Threshold::AbsoluteCount { weight: Uint128::new(6) }),
+                threshold: None,
+                token: None,
+                nft: None,
+                merkle_root: None,
+                msgs: vec![],
+            };
+            let _res = execute(deps.as_mut(), env.clone(), creator.clone(), msg).unwrap();
+
+            let msg = ExecuteMsg::Vote {
+                poll_id,
+                vote: vec![vote.to_string()],
+                proof: None,
+            };
+            let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+
+        // The creator gets the deposit back from a poll that passed
+        let msg = ExecuteMsg::ClosePoll { poll_id: 1 };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: ADDR2.to_string(),
+                amount: vec![Coin::new(10, "ukuji")],
+            })]
+        );
+
+        // A vetoed poll loses its deposit to the treasury
+        let msg = ExecuteMsg::ClosePoll { poll_id: 2 };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert!(res.messages.is_empty());
+
+        let msg = QueryMsg::Poll { poll_id: 2 };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.poll.unwrap().result,
+            Some(PollResult { outcome: Outcome::Vetoed, winner: None })
+        );
+
+        // 7ukuji of fees plus the 10ukuji deposit
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Treasury {}).unwrap();
+        let res: TreasuryResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.balances, vec![Coin::new(16, "ukuji")]);
+
+        // So does a poll nobody votes on, under the burn setting it was created with
+        let mut no_votes = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "Should we upgrade?".to_string(),
+            options: vec!["Yes".to_string(), "No".to_string(), "NoWithVeto".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: Some(Threshold::AbsoluteCount { weight: Uint128::new(6) }),
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), creator.clone(), no_votes.clone()).unwrap();
+        let mut deposit = DepositConfig {
+            amount: Uint128::new(10),
+            open_creation: true,
+            burn: true,
+            veto: Some(Veto {
+                option: "NoWithVeto".to_string(),
+                threshold: Decimal::from_ratio(1u128, 3u128),
+            }),
+        };
+        let msg = ExecuteMsg::UpdateConfig { fees: None, deposit: Some(deposit.clone()) };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let close = ExecuteMsg::ClosePoll { poll_id: 3 };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), close).unwrap();
+        assert!(res.messages.is_empty());
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Treasury {}).unwrap();
+        let res: TreasuryResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.balances, vec![Coin::new(29, "ukuji")]);
+
+        // Veto thresholds have to be above 0 and at most 1
+        deposit.veto = Some(Veto { option: "NoWithVeto".to_string(), threshold: Decimal::zero() });
+        let msg = ExecuteMsg::UpdateConfig { fees: None, deposit: Some(deposit) };
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidThreshold {}));
+
+        // Every poll has to be open to a veto
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "Should we upgrade?".to_string(),
+            options: vec!["Yes".to_string(), "No".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let err = execute(deps.as_mut(), env.clone(), creator.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingVetoOption { .. }));
+
+        // And a quorum that can be missed
+        if let ExecuteMsg::CreatePoll { quorum, .. } = &mut no_votes {
+            *quorum = None;
+        }
+        let err = execute(deps.as_mut(), env.clone(), creator.clone(), no_votes.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NeedsQuorum {}));
+        if let ExecuteMsg::CreatePoll { quorum, .. } = &mut no_votes {
+            *quorum = Some(Threshold::AbsoluteCount { weight: Uint128::zero() });
+        }
+        let err = execute(deps.as_mut(), env.clone(), creator.clone(), no_votes).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidQuorum {}));
+
+        // Only poll creators can attach messages, even with open creation
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "Should we pay addr2?".to_string(),
+            options: vec!["Yes".to_string(), "No".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: Some(Threshold::AbsoluteCount { weight: Uint128::new(6) }),
+            threshold: Some(Threshold::VotesCast { percentage: Decimal::percent(50) }),
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![BankMsg::Send {
+                to_address: ADDR2.to_string(),
+                amount: vec![Coin::new(16, "ukuji")],
+            }
+            .into()],
+        };
+        let err = execute(deps.as_mut(), env.clone(), creator, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { .. }));
+
+        // The fee alone doesn't cover the deposit
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "Should we upgrade?".to_string(),
+            options: vec!["Yes".to_string(), "No".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let info = mock_info(ADDR2, &[Coin::new(10, "ukuji")]);
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFee { fee, .. } if fee == Uint128::new(11)));
+    }
+
+    #[test]
+    fn test_roles() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract, ADDR1 becomes the owner
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // The owner makes ADDR2 a moderator, who makes LFG a poll closer
+        let msg = ExecuteMsg::GrantRole { address: ADDR2.to_string(), role: Role::Moderator };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let moderator = mock_info(ADDR2, &[Coin::new(1, "ukuji")]);
+        let msg = ExecuteMsg::GrantRole { address: LFG.to_string(), role: Role::PollCloser };
+        let _res = execute(deps.as_mut(), env.clone(), moderator.clone(), msg).unwrap();
+
+        let msg = QueryMsg::RoleHolders { role: Role::PollCloser };
+        let bin = query(deps.as_ref(), env.clone(), msg.clone()).unwrap();
+        let res: RoleHoldersResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.holders, vec![Addr::unchecked(LFG)]);
+
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+        // Closers can close polls but not create them
+        let closer = mock_info(LFG, &[Coin::new(1, "ukuji")]);
+        let err = execute(deps.as_mut(), env.clone(), closer.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { .. }));
+        let msg = ExecuteMsg::ClosePoll { poll_id: 1 };
+        let _res = execute(deps.as_mut(), env.clone(), closer, msg).unwrap();
+
+        let msg = ExecuteMsg::RevokeRole { address: LFG.to_string(), role: Role::PollCloser };
+        let _res = execute(deps.as_mut(), env.clone(), moderator.clone(), msg).unwrap();
+        let msg = QueryMsg::RoleHolders { role: Role::PollCloser };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: RoleHoldersResponse = from_binary(&bin).unwrap();
+        assert!(res.holders.is_empty());
+
+        // Moderators can't hand out the other roles
+        let msg = ExecuteMsg::GrantRole { address: ADDR2.to_string(), role: Role::FeeManager };
+        let err = execute(deps.as_mut(), env, moderator, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { .. }));
+    }
+
+    #[test]
+    fn test_ownership_transfer() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract, ADDR1 becomes the owner
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        for role in [Role::Moderator, Role::PollCloser] {
+            let msg = ExecuteMsg::GrantRole { address: ADDR2.to_string(), role };
+            let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Admins {}).unwrap();
+        let res: AdminsResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.admins,
+            vec![
+                AdminInfo { address: Addr::unchecked(ADDR1), roles: vec![Role::Owner] },
+                AdminInfo {
+                    address: Addr::unchecked(ADDR2),
+                    roles: vec![Role::Moderator, Role::PollCloser],
+                },
+            ]
+        );
+
+        // LFG has ten blocks to accept
+        let msg = ExecuteMsg::ProposeOwner {
+            new_owner: LFG.to_string(),
+            expiry: Some(Expiration::AtHeight(env.block.height + 10)),
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let new_owner = mock_info(LFG, &[Coin::new(1, "ukuji")]);
+        let msg = ExecuteMsg::AcceptOwnership {};
+        let _res = execute(deps.as_mut(), env.clone(), new_owner.clone(), msg).unwrap();
+
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Ownership {}).unwrap();
+        let res: OwnershipResponse = from_binary(&bin).unwrap();
+        assert_eq!(res, OwnershipResponse { owner: Some(Addr::unchecked(LFG)), pending_owner: None });
+
+        // The new owner removes ADDR2's roles
+        let msg = ExecuteMsg::RemoveAdmin { address: ADDR2.to_string() };
+        let _res = execute(deps.as_mut(), env.clone(), new_owner.clone(), msg).unwrap();
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Admins {}).unwrap();
+        let res: AdminsResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.admins,
+            vec![AdminInfo { address: Addr::unchecked(LFG), roles: vec![Role::Owner] }]
+        );
+
+        // A transfer can't be accepted once it expires
+        let msg = ExecuteMsg::ProposeOwner {
+            new_owner: ADDR1.to_string(),
+            expiry: Some(Expiration::AtHeight(env.block.height + 1)),
+        };
+        let _res = execute(deps.as_mut(), env.clone(), new_owner, msg).unwrap();
+        env.block.height += 1;
+        let msg = ExecuteMsg::AcceptOwnership {};
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::TransferExpired {}));
+
+        // The old owner lost every permission
+        let msg = ExecuteMsg::GrantRole { address: ADDR2.to_string(), role: Role::Owner };
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { .. }));
+    }
+
+    #[test]
+    fn test_execute_vote_group() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let height = env.block.height;
+        // ADDR2 only joins the group after the first poll is created
+        mock_group(&mut deps, &[(ADDR1, 5, 0), (ADDR2, 7, height + 5)]);
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        // Instantiate the contract
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: Some("group".to_string()),
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+
+        // Members vote with their group weight
+        let vote = ExecuteMsg::Vote { poll_id: 1, vote: vec!["Juno".to_string()], proof: None };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), vote).unwrap();
+        let query_msg = QueryMsg::Vote { poll_id: 1, address: ADDR1.to_string() };
+        let bin = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let res: VoteResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.vote.unwrap().weight, Uint128::new(5));
+
+        // A poll created after ADDR2 joined counts them
+        env.block.height += 10;
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let info = mock_info(ADDR2, &[Coin::new(1, "ukuji")]);
+        let vote = ExecuteMsg::Vote { poll_id: 2, vote: vec!["Osmosis".to_string()], proof: None };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), vote).unwrap();
+        let query_msg = QueryMsg::Vote { poll_id: 2, address: ADDR2.to_string() };
+        let bin = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let res: VoteResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.vote.unwrap().weight, Uint128::new(7));
+
+        // Eligible power comes from the group at the snapshot, whatever the creator claims
+        let msg = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: Some(Threshold::EligiblePower {
+                percentage: Decimal::percent(50),
+                eligible: Uint128::new(1000),
+            }),
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let owner = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        let _res = execute(deps.as_mut(), env.clone(), owner, msg).unwrap();
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Poll { poll_id: 3 }).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.poll.unwrap().quorum,
+            Some(Threshold::EligiblePower {
+                percentage: Decimal::percent(50),
+                eligible: Uint128::new(12),
+            })
+        );
+
+        // But the first poll was snapshotted before they joined
+        let vote = ExecuteMsg::Vote { poll_id: 1, vote: vec!["Osmosis".to_string()], proof: None };
+        let err = execute(deps.as_mut(), env, info, vote).unwrap_err();
+        assert!(matches!(err, ContractError::NotMember {}));
+    }
+
+    // Answers cw721 OwnerOf queries for a collection from a list of token ids and their owners
+    fn mock_nfts(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, tokens: &[(&str, &str)]) {
+        let tokens: Vec<(String, String)> = tokens
+            .iter()
+            .map(|(token_id, owner)| (token_id.to_string(), owner.to_string()))
+            .collect();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { msg, .. } => {
+                let Cw721QueryMsg::OwnerOf { token_id, .. } = from_binary(msg).unwrap();
+                match tokens.iter().find(|token| token.0 == token_id) {
+                    Some((_, owner)) => {
+                        let res = OwnerOfResponse { owner: owner.clone(), approvals: vec![] };
+                        SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+                    }
+                    None => SystemResult::Ok(ContractResult::Err("Token not found".to_string())),
+                }
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest { kind: "wasm".to_string() }),
+        });
     }
 
     #[test]
-    fn test_execute_vote_valid() {
+    fn test_execute_vote_nft() {
         let mut deps = mock_dependencies();
+        mock_nfts(&mut deps, &[("1", ADDR1), ("2", ADDR1), ("3", ADDR1), ("4", ADDR2)]);
         let env = mock_env();
         let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
         // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
         let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
-        // Create the poll
         let msg = ExecuteMsg::CreatePoll {
-            poll_id: "some_id".to_string(),
+            external_ref: None,
             question: "What's your favourite Cosmos coin?".to_string(),
-            options: vec![
-                "Cosmos Hub".to_string(),
-                "Juno".to_string(),
-                "Osmosis".to_string(),
-            ],
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: Some("collection".to_string()),
+            merkle_root: None,
+            msgs: vec![],
         };
         let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
-        // Create the vote, first time voting
-        let msg = ExecuteMsg::Vote {
-            poll_id: "some_id".to_string(),
-            vote: "Osmosis".to_string(),
+        // Every token is a ballot of its own
+        let vote = |token_ids: &[&str], option: &str| ExecuteMsg::NftVote {
+            poll_id: 1,
+            token_ids: token_ids.iter().map(|token_id| token_id.to_string()).collect(),
+            vote: vec![option.to_string()],
         };
-        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        let msg = vote(&["1", "2", "3"], "Juno");
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(res.events.len(), 3);
 
+        // Once token 1 is transferred its new owner replaces its ballot instead of adding one
+        mock_nfts(&mut deps, &[("1", ADDR2), ("2", ADDR1), ("3", ADDR1), ("4", ADDR2)]);
+        let info2 = mock_info(ADDR2, &[Coin::new(1, "ukuji")]);
+        let msg = vote(&["1", "4"], "Osmosis");
+        let _res = execute(deps.as_mut(), env.clone(), info2, msg).unwrap();
+        let msg = QueryMsg::Poll { poll_id: 1 };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        let poll = res.poll.unwrap();
         assert_eq!(
-            res.attributes,
-            vec![attr("action", "vote on poll"), attr("on:", "Osmosis")],
+            poll.options,
+            vec![("Juno".to_string(), Uint128::new(2)), ("Osmosis".to_string(), Uint128::new(2))]
         );
-    }
-
-    // #[test]
-    // fn test_execute_vote_invalid() {
-    //     let mut deps = mock_dependencies();
-    //     let env = mock_env();
-    //     let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
-    //     // Instantiate the contract
-    //     let msg = InstantiateMsg { admin: None };
-    //     let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
-
-    //     // Create the vote, some_id poll is not created yet.
-    //     let msg = ExecuteMsg::Vote {
-    //         poll_id: "some_id".to_string(),
-    //         vote: "Juno".to_string(),
-    //     };
-    //     // Unwrap to assert error
-    //     let _err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(poll.turnout, Uint128::new(4));
 
-    //     // Create the poll
-    //     let msg = ExecuteMsg::CreatePoll {
-    //         poll_id: "some_id".to_string(),
-    //         question: "What's your favourite Cosmos coin?".to_string(),
-    //         options: vec![
-    //             "Cosmos Hub".to_string(),
-    //             "Juno".to_string(),
-    //             "Osmosis".to_string(),
-    //         ],
-    //     };
-    //     let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = QueryMsg::NftVote { poll_id: 1, token_id: "1".to_string() };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: NftVoteResponse = from_binary(&bin).unwrap();
+        let nft_ballot = res.vote.unwrap();
+        assert_eq!(nft_ballot.voter, Addr::unchecked(ADDR2));
+        assert_eq!(nft_ballot.ballot.options, vec!["Osmosis".to_string()]);
 
-    //     // Vote on a now existing poll but the option "DVPN" does not exist
-    //     let msg = ExecuteMsg::Vote {
-    //         poll_id: "some_id".to_string(),
-    //         vote: "DVPN".to_string(),
-    //     };
+        // NFT ballots are listed by token id
+        let msg = QueryMsg::NftBallots { poll_id: 1, start_after: None, limit: Some(3) };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: NftBallotsResponse = from_binary(&bin).unwrap();
+        let tokens: Vec<_> = res.ballots.iter().map(|(token_id, _)| token_id.as_str()).collect();
+        assert_eq!(tokens, vec!["1", "2", "3"]);
+        assert_eq!(res.ballots[0].1.voter, Addr::unchecked(ADDR2));
+        let msg = QueryMsg::NftBallots {
+            poll_id: 1,
+            start_after: Some("3".to_string()),
+            limit: None,
+        };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: NftBallotsResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.ballots.len(), 1);
+        assert_eq!(res.ballots[0].0, "4");
 
-    //     let _err = execute(deps.as_mut(), env, info, msg).unwrap_err();
-    // }
+        // Tokens owned by someone else, listed twice or plain votes are turned away
+        let msg = vote(&["1"], "Juno");
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotTokenOwner { .. }));
+        let msg = vote(&["2", "2"], "Juno");
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTokenIds {}));
+        let msg = ExecuteMsg::Vote { poll_id: 1, vote: vec!["Juno".to_string()], proof: None };
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NftPoll {}));
+    }
 
     #[test]
-    fn test_query_all_polls() {
+    fn test_execute_vote_nft_counted() {
         let mut deps = mock_dependencies();
+        mock_nfts(&mut deps, &[("1", ADDR1), ("2", ADDR1), ("3", ADDR2)]);
         let env = mock_env();
         let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
         // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
         let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
-        // Create a poll
-        let msg = ExecuteMsg::CreatePoll {
-            poll_id: "some_id_1".to_string(),
-            question: "What's your favourite Cosmos coin?".to_string(),
-            options: vec![
-                "Cosmos Hub".to_string(),
-                "Juno".to_string(),
-                "Osmosis".to_string(),
-            ],
+        // A ranked choice poll and a quadratic poll with 4 credits per token
+        for poll_type in [PollType::RankedChoice, PollType::Quadratic { credits: 4 }] {
+            let msg = ExecuteMsg::CreatePoll {
+                external_ref: None,
+                question: "What's your favourite Cosmos coin?".to_string(),
+                options: vec!["Juno".to_string(), "Osmosis".to_string()],
+                poll_type,
+                category: None,
+                start: None,
+                end: None,
+                reveal_end: None,
+                quorum: None,
+                threshold: None,
+                token: None,
+                nft: Some("collection".to_string()),
+                merkle_root: None,
+                msgs: vec![],
+            };
+            let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+        let vote = |poll_id, token_ids: &[&str], options: &[&str]| ExecuteMsg::NftVote {
+            poll_id,
+            token_ids: token_ids.iter().map(|token_id| token_id.to_string()).collect(),
+            vote: options.iter().map(|option| option.to_string()).collect(),
         };
+
+        // The runoff counts every token's ranking
+        let msg = vote(1, &["1", "2"], &["Juno", "Osmosis"]);
         let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = vote(1, &["3"], &["Osmosis", "Juno"]);
+        let info2 = mock_info(ADDR2, &[Coin::new(1, "ukuji")]);
+        let _res = execute(deps.as_mut(), env.clone(), info2, msg).unwrap();
+        let msg = ExecuteMsg::ClosePoll { poll_id: 1 };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Poll { poll_id: 1 }).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.poll.unwrap().result,
+            Some(PollResult { outcome: Outcome::Passed, winner: Some("Juno".to_string()) })
+        );
 
-        // Create a second poll
-        let msg = ExecuteMsg::CreatePoll {
-            poll_id: "some_id_2".to_string(),
-            question: "What's your colour?".to_string(),
-            options: vec!["Red".to_string(), "Green".to_string(), "Blue".to_string()],
-        };
+        // Each token spends its own credits
+        let msg = vote(2, &["1"], &["Juno", "Juno"]);
         let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
-
-        let msg = QueryMsg::AllPolls {};
-        let bin = query(deps.as_ref(), env, msg).unwrap();
-        let res: AllPollsResponse = from_binary(&bin).unwrap();
-        assert_eq!(res.polls.len(), 2);
-    }
-
-    #[test]
-    fn test_query_all_polls_with_no_polls_created() {
-        let mut deps = mock_dependencies();
-        let env = mock_env();
-        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
-        // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
-        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
-
-        let msg = QueryMsg::AllPolls {};
-        let bin = query(deps.as_ref(), env, msg).unwrap();
-        let res: AllPollsResponse = from_binary(&bin).unwrap();
-        assert_eq!(res.polls.len(), 0);
+        for (token_id, remaining) in [("1", 0), ("2", 4)] {
+            let msg = QueryMsg::Credits {
+                poll_id: 2,
+                address: ADDR1.to_string(),
+                token_id: Some(token_id.to_string()),
+            };
+            let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+            let res: CreditsResponse = from_binary(&bin).unwrap();
+            assert_eq!(res.remaining, Some(remaining));
+        }
     }
 
     #[test]
-    fn test_query_poll() {
+    fn test_execute_vote_allowlist() {
         let mut deps = mock_dependencies();
         let env = mock_env();
         let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
         // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
         let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
-        // Create a poll
+        // Two leaves, ADDR1 with a weight of 5 and ADDR2 without one
+        let leaf1 = allowlist_leaf(&Addr::unchecked(ADDR1), Some(Uint128::new(5)));
+        let leaf2 = allowlist_leaf(&Addr::unchecked(ADDR2), None);
+        let root = allowlist_node(&leaf1, &leaf2);
+        // The leaves are hashed as documented on AllowlistProof
+        let mut preimage = vec![0u8];
+        preimage.extend_from_slice(b"addr1:5");
+        assert_eq!(leaf1, Sha256::digest(&preimage).to_vec());
+        // so a weight can't be moved into the address
+        assert_ne!(
+            allowlist_leaf(&Addr::unchecked("addr1"), Some(Uint128::new(23))),
+            allowlist_leaf(&Addr::unchecked("addr12"), Some(Uint128::new(3)))
+        );
+
         let msg = ExecuteMsg::CreatePoll {
-            poll_id: "some_id_1".to_string(),
+            external_ref: None,
             question: "What's your favourite Cosmos coin?".to_string(),
-            options: vec![
-                "Cosmos Hub".to_string(),
-                "Juno".to_string(),
-                "Osmosis".to_string(),
-            ],
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: Some(Binary::from(root)),
+            msgs: vec![],
         };
-        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
-        // Query for the poll that exists
-        let msg = QueryMsg::Poll {
-            poll_id: "some_id_1".to_string(),
+        let vote = |option: &str, sibling: &[u8], weight: Option<u128>| ExecuteMsg::Vote {
+            poll_id: 1,
+            vote: vec![option.to_string()],
+            proof: Some(AllowlistProof {
+                proof: vec![Binary::from(sibling)],
+                weight: weight.map(Uint128::new),
+            }),
         };
-        let bin = query(deps.as_ref(), env, msg).unwrap();
+        let msg = vote("Juno", &leaf2, Some(5));
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let info2 = mock_info(ADDR2, &[Coin::new(1, "ukuji")]);
+        let msg = vote("Osmosis", &leaf1, None);
+        let _res = execute(deps.as_mut(), env.clone(), info2.clone(), msg).unwrap();
+        let msg = QueryMsg::Poll { poll_id: 1 };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
         let res: PollResponse = from_binary(&bin).unwrap();
-        // Expect a poll
-        assert!(res.poll.is_some());
+        let poll = res.poll.unwrap();
+        assert_eq!(
+            poll.options,
+            vec![("Juno".to_string(), Uint128::new(5)), ("Osmosis".to_string(), Uint128::new(1))]
+        );
+
+        // Missing proofs, claiming someone else's weight or addresses off the list are turned away
+        let msg = ExecuteMsg::Vote { poll_id: 1, vote: vec!["Juno".to_string()], proof: None };
+        let err = execute(deps.as_mut(), env.clone(), info2.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted {}));
+        let msg = vote("Juno", &leaf1, Some(5));
+        let err = execute(deps.as_mut(), env.clone(), info2, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted {}));
+        let info3 = mock_info("addr3", &[Coin::new(1, "ukuji")]);
+        let msg = vote("Juno", &leaf2, None);
+        let err = execute(deps.as_mut(), env, info3, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted {}));
     }
 
     #[test]
-    fn test_query_poll_fail() {
+    fn test_execute_vote_token_weighted() {
         let mut deps = mock_dependencies();
         let env = mock_env();
         let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
         // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
         let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
-        // Create a poll
         let msg = ExecuteMsg::CreatePoll {
-            poll_id: "some_id_1".to_string(),
+            external_ref: None,
             question: "What's your favourite Cosmos coin?".to_string(),
-            options: vec![
-                "Cosmos Hub".to_string(),
-                "Juno".to_string(),
-                "Osmosis".to_string(),
-            ],
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: Some("token".to_string()),
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
         };
-        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
-        // Query for the poll that exists
-        let msg = QueryMsg::Poll {
-            poll_id: "some_id_1".to_string(),
+        // The token contract forwards the vote and the amount sent becomes its weight
+        let token = mock_info("token", &[]);
+        let send = |amount: u128, option: &str| {
+            let vote = ReceiveMsg::Vote { poll_id: 1, vote: vec![option.to_string()] };
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: ADDR2.to_string(),
+                amount: Uint128::new(amount),
+                msg: to_binary(&vote).unwrap(),
+            })
         };
+        let _res = execute(deps.as_mut(), env.clone(), token.clone(), send(100, "Juno")).unwrap();
+
+        // A revote adds the new tokens to the escrow and moves all of them
+        let _res = execute(deps.as_mut(), env.clone(), token.clone(), send(50, "Osmosis")).unwrap();
+        let msg = QueryMsg::Poll { poll_id: 1 };
         let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
         let res: PollResponse = from_binary(&bin).unwrap();
-        // Expect a poll
-        assert!(res.poll.is_some());
+        assert_eq!(
+            res.poll.unwrap().options,
+            vec![("Juno".to_string(), Uint128::zero()), ("Osmosis".to_string(), Uint128::new(150))]
+        );
+        let msg = QueryMsg::Escrow { poll_id: 1, address: ADDR2.to_string() };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: EscrowResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.amount, Uint128::new(150));
 
-        // Query for the poll that does not exists
-        let msg = QueryMsg::Poll {
-            poll_id: "some_id_does_not_exist".to_string(),
+        // Other tokens and plain votes are turned away
+        let other = mock_info("other_token", &[]);
+        let err = execute(deps.as_mut(), env.clone(), other, send(10, "Juno")).unwrap_err();
+        assert!(matches!(err, ContractError::NotTokenPoll {}));
+        let msg = ExecuteMsg::Vote { poll_id: 1, vote: vec!["Juno".to_string()], proof: None };
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::TokenPoll {}));
+
+        // Tokens stay escrowed until the poll ends
+        let voter = mock_info(ADDR2, &[]);
+        let msg = ExecuteMsg::ClaimTokens { poll_id: 1 };
+        let err = execute(deps.as_mut(), env.clone(), voter.clone(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::PollNotClosed {}));
+
+        let close = ExecuteMsg::ClosePoll { poll_id: 1 };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), close).unwrap();
+        let res = execute(deps.as_mut(), env.clone(), voter.clone(), msg.clone()).unwrap();
+        let transfer = Cw20ExecuteMsg::Transfer {
+            recipient: ADDR2.to_string(),
+            amount: Uint128::new(150),
         };
-        let bin = query(deps.as_ref(), env, msg).unwrap();
-        let res: PollResponse = from_binary(&bin).unwrap();
-        assert!(res.poll.is_none());
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token".to_string(),
+                msg: to_binary(&transfer).unwrap(),
+                funds: vec![],
+            })]
+        );
+
+        // Once a poll has ended its tokens can be claimed without it being closed
+        let create = ExecuteMsg::CreatePoll {
+            external_ref: None,
+            question: "What's your favourite Cosmos coin?".to_string(),
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: Some(Expiration::AtHeight(env.block.height + 10)),
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: Some("token".to_string()),
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, create).unwrap();
+        let vote = ReceiveMsg::Vote { poll_id: 2, vote: vec!["Juno".to_string()] };
+        let send = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: ADDR2.to_string(),
+            amount: Uint128::new(20),
+            msg: to_binary(&vote).unwrap(),
+        });
+        let _res = execute(deps.as_mut(), env.clone(), token, send).unwrap();
+        let mut ended = env.clone();
+        ended.block.height += 10;
+        let claim = ExecuteMsg::ClaimTokens { poll_id: 2 };
+        let res = execute(deps.as_mut(), ended, voter.clone(), claim).unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        let err = execute(deps.as_mut(), env, voter, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NoEscrow {}));
     }
 
     #[test]
-    fn test_query_vote() {
+    fn test_hooks() {
         let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1)]);
         let env = mock_env();
         let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
         // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
         let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
-        // Create a poll
+        let msg = ExecuteMsg::AddHook { addr: "rewards".to_string() };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Hooks {}).unwrap();
+        let res: HooksResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.hooks, vec!["rewards".to_string()]);
+
+        // Creating a poll, voting and closing it each call the hook
         let msg = ExecuteMsg::CreatePoll {
-            poll_id: "some_id_1".to_string(),
+            external_ref: None,
             question: "What's your favourite Cosmos coin?".to_string(),
-            options: vec![
-                "Cosmos Hub".to_string(),
-                "Juno".to_string(),
-                "Osmosis".to_string(),
-            ],
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
         };
-        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let hook = PollHookMsg::Created { poll_id: 1, creator: ADDR1.to_string() };
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(HookExecuteMsg::PollHook(hook).into_cosmos_msg("rewards").unwrap())]
+        );
 
-        // Create a vote
-        let msg = ExecuteMsg::Vote {
-            poll_id: "some_id_1".to_string(),
-            vote: "Juno".to_string(),
+        let msg = ExecuteMsg::Vote { poll_id: 1, vote: vec!["Juno".to_string()], proof: None };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Vote { poll_id: 1, vote: vec!["Osmosis".to_string()], proof: None };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let hook = VoteHookMsg {
+            poll_id: 1,
+            voter: ADDR1.to_string(),
+            token_id: None,
+            previous: Some(vec!["Juno".to_string()]),
+            options: vec!["Osmosis".to_string()],
+            weight: Uint128::new(1),
         };
-        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(HookExecuteMsg::VoteHook(hook).into_cosmos_msg("rewards").unwrap())]
+        );
 
-        let msg = QueryMsg::Vote {
-            poll_id: "some_id_1".to_string(),
-            address: ADDR1.to_string(),
+        let msg = ExecuteMsg::ClosePoll { poll_id: 1 };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let hook = PollHookMsg::Closed {
+            poll_id: 1,
+            outcome: Outcome::Passed,
+            winner: Some("Osmosis".to_string()),
         };
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(HookExecuteMsg::PollHook(hook).into_cosmos_msg("rewards").unwrap())]
+        );
 
-        let bin = query(deps.as_ref(), env, msg).unwrap();
-        let res: VoteResponse = from_binary(&bin).unwrap();
-        assert!(res.vote.is_some());
+        // Only the owner manages hooks
+        let msg = ExecuteMsg::AddHook { addr: "reputation".to_string() };
+        let info2 = mock_info(ADDR2, &[Coin::new(1, "ukuji")]);
+        let err = execute(deps.as_mut(), env.clone(), info2, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingRole { .. }));
+
+        let msg = ExecuteMsg::RemoveHook { addr: "rewards".to_string() };
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Hooks {}).unwrap();
+        let res: HooksResponse = from_binary(&bin).unwrap();
+        assert!(res.hooks.is_empty());
+
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::HookNotRegistered {}));
     }
 
-    // Previous code omitted
     #[test]
-    fn test_query_vote_fail() {
+    fn test_migrate_from_v0_1_0() {
         let mut deps = mock_dependencies();
         let env = mock_env();
-        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
-        // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
-        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
-        // Create a poll
+        // State as 0.1.0 left it, with string poll ids and a list of admins
+        set_contract_version(deps.as_mut().storage, "crates.io:cw-starter", "0.1.0").unwrap();
+        let config = v0_1_0::Config { admins: vec![Addr::unchecked(LFG), Addr::unchecked(ADDR1)] };
+        v0_1_0::CONFIG.save(deps.as_mut().storage, &config).unwrap();
+        for (poll_id, votes, closed) in [("some_id_1", [1, 0], false), ("some_id_2", [0, 2], true)] {
+            let poll = v0_1_0::Poll {
+                creator: Addr::unchecked(LFG),
+                question: "What's your favourite Cosmos coin?".to_string(),
+                options: vec![("Juno".to_string(), votes[0]), ("Osmosis".to_string(), votes[1])],
+                closed,
+            };
+            v0_1_0::POLLS.save(deps.as_mut().storage, poll_id, &poll).unwrap();
+        }
+        for (voter, poll_id, option) in [
+            (ADDR1, "some_id_1", "Juno"),
+            (ADDR1, "some_id_2", "Osmosis"),
+            (ADDR2, "some_id_2", "Osmosis"),
+        ] {
+            let ballot = v0_1_0::Ballot { option: option.to_string() };
+            v0_1_0::BALLOTS
+                .save(deps.as_mut().storage, (Addr::unchecked(voter), poll_id), &ballot)
+                .unwrap();
+        }
+
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        assert_eq!(res.attributes[1], attr("from_version", "0.1.0"));
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+
+        // Polls are numbered by their old ids, which become their external reference
+        let msg = QueryMsg::ExternalRef { external_ref: "some_id_2".to_string() };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: ExternalRefResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.poll_id, Some(2));
+
+        let msg = QueryMsg::Poll { poll_id: 2 };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        let poll = res.poll.unwrap();
+        assert_eq!(poll.turnout, Uint128::new(2));
+        assert_eq!(
+            poll.result,
+            Some(PollResult { outcome: Outcome::Passed, winner: Some("Osmosis".to_string()) })
+        );
+
+        let msg = QueryMsg::Vote { poll_id: 2, address: ADDR2.to_string() };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: VoteResponse = from_binary(&bin).unwrap();
+        assert_eq!(
+            res.vote,
+            Some(Ballot { poll_id: 2, options: vec!["Osmosis".to_string()], weight: Uint128::new(1) })
+        );
+
+        let msg = QueryMsg::Ballots { poll_id: 1, start_after: None, limit: None };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: BallotsResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.ballots.len(), 1);
+
+        // The first admin owns the contract and the others can create polls
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Ownership {}).unwrap();
+        let res: OwnershipResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.owner, Some(Addr::unchecked(LFG)));
+        let msg = QueryMsg::RoleHolders { role: Role::PollCreator };
+        let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let res: RoleHoldersResponse = from_binary(&bin).unwrap();
+        assert_eq!(res.holders, vec![Addr::unchecked(ADDR1)]);
+
+        // New polls carry on from the migrated ones
         let msg = ExecuteMsg::CreatePoll {
-            poll_id: "some_id_1".to_string(),
+            external_ref: None,
             question: "What's your favourite Cosmos coin?".to_string(),
-            options: vec![
-                "Cosmos Hub".to_string(),
-                "Juno".to_string(),
-                "Osmosis".to_string(),
-            ],
+            options: vec!["Juno".to_string(), "Osmosis".to_string()],
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            reveal_end: None,
+            quorum: None,
+            threshold: None,
+            token: None,
+            nft: None,
+            merkle_root: None,
+            msgs: vec![],
         };
-        let _res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert_eq!(res.data, Some(to_binary(&3u64).unwrap()));
 
-        // Create a vote
-        let msg = ExecuteMsg::Vote {
-            poll_id: "some_id_1".to_string(),
-            vote: "Juno".to_string(),
-        };
-        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        // Migrating again doesn't touch the state
+        migrate(deps.as_mut(), env, MigrateMsg {}).unwrap();
+    }
 
-        // Query for a vote that exists
-        let msg = QueryMsg::Vote {
-            poll_id: "some_id_1".to_string(),
-            address: ADDR1.to_string(),
+    #[test]
+    fn test_migrate_overwritten_poll() {
+        let mut deps = mock_dependencies();
+        mock_stake(&mut deps, &[(ADDR1, 1), (ADDR2, 1)]);
+        let env = mock_env();
+
+        // Poll "p" was created again with new options, which reset its counts and left
+        // ADDR1's ballot for an option it no longer has
+        set_contract_version(deps.as_mut().storage, "crates.io:cw-starter", "0.1.0").unwrap();
+        let config = v0_1_0::Config { admins: vec![Addr::unchecked(ADDR1)] };
+        v0_1_0::CONFIG.save(deps.as_mut().storage, &config).unwrap();
+        let poll = v0_1_0::Poll {
+            creator: Addr::unchecked(ADDR1),
+            question: "A or B?".to_string(),
+            options: vec![("A".to_string(), 0), ("B".to_string(), 0)],
+            closed: false,
         };
+        v0_1_0::POLLS.save(deps.as_mut().storage, "p", &poll).unwrap();
+        for (voter, option) in [(ADDR1, "Old"), (ADDR2, "A")] {
+            let ballot = v0_1_0::Ballot { option: option.to_string() };
+            v0_1_0::BALLOTS
+                .save(deps.as_mut().storage, (Addr::unchecked(voter), "p"), &ballot)
+                .unwrap();
+        }
+        migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+
+        // The ballot for the old option is dropped and the tallies follow the ballots left
+        let msg = QueryMsg::Vote { poll_id: 1, address: ADDR1.to_string() };
         let bin = query(deps.as_ref(), env.clone(), msg).unwrap();
         let res: VoteResponse = from_binary(&bin).unwrap();
-        // Expect the vote to exist
-        assert!(res.vote.is_some());
+        assert_eq!(res.vote, None);
+        let bin = query(deps.as_ref(), env.clone(), QueryMsg::Poll { poll_id: 1 }).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        let poll = res.poll.unwrap();
+        assert_eq!(
+            poll.options,
+            vec![("A".to_string(), Uint128::new(1)), ("B".to_string(), Uint128::zero())]
+        );
+        assert_eq!(poll.turnout, Uint128::new(1));
 
-        // Query for a vote that does not exists
-        let msg = QueryMsg::Vote {
-            poll_id: "some_id_1".to_string(),
-            address: ADDR2.to_string(),
-        };
+        // Both voters can vote again
+        for voter in [ADDR1, ADDR2] {
+            let msg = ExecuteMsg::Vote { poll_id: 1, vote: vec!["B".to_string()], proof: None };
+            let info = mock_info(voter, &[Coin::new(1, "ukuji")]);
+            let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+        let bin = query(deps.as_ref(), env, QueryMsg::Poll { poll_id: 1 }).unwrap();
+        let res: PollResponse = from_binary(&bin).unwrap();
+        let poll = res.poll.unwrap();
+        assert_eq!(
+            poll.options,
+            vec![("A".to_string(), Uint128::zero()), ("B".to_string(), Uint128::new(2))]
+        );
+    }
 
-        let bin = query(deps.as_ref(), env, msg).unwrap();
-        let res: VoteResponse = from_binary(&bin).unwrap();
+    #[test]
+    fn test_migrate_invalid() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
 
-        assert!(res.vote.is_none())
+        set_contract_version(deps.as_mut().storage, "crates.io:cw-starter", "9.0.0").unwrap();
+        let err = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotDowngrade { .. }));
+
+        set_contract_version(deps.as_mut().storage, "crates.io:cw20-base", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), env, MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::WrongContract { .. }));
     }
 
     #[test]
@@ -667,7 +4896,13 @@ mod tests {
         let env = mock_env();
         let info = mock_info(LFG, &[Coin::new(1, "ukuji")]);
         // Instantiate the contract
-        let msg = InstantiateMsg { admin: None };
+        let msg = InstantiateMsg {
+            admin: None,
+            validators: None,
+            fees: None,
+            deposit: None,
+            group_addr: None,
+        };
         instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
         let info = mock_info(ADDR1, &[Coin::new(1, "ukuji")]);
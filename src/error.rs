@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
+use cw_utils::PaymentError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,19 +7,37 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
 
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("This action needs the {role} role.")]
+    MissingRole { role: String },
+
+    #[error("Ownership can only be transferred with ProposeOwner and AcceptOwnership.")]
+    OwnerRole {},
+
+    #[error("There is no pending ownership transfer for this address.")]
+    NoPendingOwner {},
+
+    #[error("The ownership transfer has expired.")]
+    TransferExpired {},
+
     #[error("Too many poll options")]
     TooManyOptions {},
 
-    #[error("Insufficient Kuji provided, please send at least 1.")]
-    BadDenom{},
+    #[error("Insufficient fee provided, please send at least {fee}{denom}.")]
+    InsufficientFee { fee: Uint128, denom: String },
+
+    #[error("Treasury only holds {available}{denom}.")]
+    InsufficientTreasury { available: Uint128, denom: String },
 
-    #[error("This address is not staked with kujiDAO.")]
+    #[error("This address has no delegated stake to vote with.")]
     NotStaked{},
 
     #[error("Poll has already been closed.")]
@@ -27,6 +46,132 @@ pub enum ContractError {
     #[error("Poll does not exist.")]
     NoPoll{},
 
+    #[error("A poll with reference {external_ref} already exists.")]
+    DuplicateExternalRef { external_ref: String },
+
+    #[error("Poll has not opened for voting yet.")]
+    PollNotStarted {},
+
+    #[error("Polls need a {option} option so they can be vetoed.")]
+    MissingVetoOption { option: String },
+
+    #[error("Poll voting period is invalid.")]
+    InvalidPeriod {},
+
+    #[error("Threshold percentages must be above 0 and at most 1.")]
+    InvalidThreshold {},
+
+    #[error("This quorum would always be reached.")]
+    InvalidQuorum {},
+
+    #[error("Polls with a deposit need a quorum.")]
+    NeedsQuorum {},
+
+    #[error("Only plurality polls with a yes and a no option can carry messages.")]
+    NotExecutable {},
+
+    #[error("Polls carrying messages need a quorum and a threshold.")]
+    NeedsThreshold {},
+
+    #[error("Poll messages can't be sent to a token escrowed by token weighted polls.")]
+    EscrowedToken {},
+
+    #[error("Poll has not passed with its yes option.")]
+    NotPassed {},
+
+    #[error("Poll has already been executed.")]
+    AlreadyExecuted {},
+
+    #[error("Option {option} is not part of this poll.")]
+    InvalidOption { option: String },
+
+    #[error("Ballot does not fit this poll.")]
+    InvalidBallot {},
+
+    #[error("Too many options selected, at most {max_selections} allowed.")]
+    TooManySelections { max_selections: u32 },
+
+    #[error("Approval polls must allow at least one selection.")]
+    NoSelections {},
+
+    #[error("Ballot costs {cost} credits but only {credits} are available.")]
+    NotEnoughCredits { cost: u64, credits: u64 },
+
+    #[error("This poll takes secret ballots, use Commit and Reveal.")]
+    CommitRevealPoll {},
+
+    #[error("This poll does not take secret ballots.")]
+    NotCommitRevealPoll {},
+
+    #[error("Poll is not in its reveal phase.")]
+    NotRevealing {},
+
+    #[error("Poll can't be closed before its reveal phase ends.")]
+    RevealNotEnded {},
+
+    #[error("This address has no commitment on this poll.")]
+    NoCommit {},
+
+    #[error("Vote and salt do not match the commitment.")]
+    InvalidReveal {},
+
+    #[error("Delegating to this address would create a cycle.")]
+    DelegationCycle {},
+
+    #[error("Allowlisted polls can't be token weighted, NFT gated or take secret ballots.")]
+    InvalidAllowlistPoll {},
+
+    #[error("This address is not on the poll's allowlist.")]
+    NotAllowlisted {},
+
+    #[error("This poll is gated by an NFT collection, vote with NftVote.")]
+    NftPoll {},
+
+    #[error("This poll is not gated by an NFT collection.")]
+    NotNftPoll {},
+
+    #[error("NFT gated polls can't be token weighted or take secret ballots.")]
+    InvalidNftPoll {},
+
+    #[error("Every token has to be listed exactly once.")]
+    InvalidTokenIds {},
+
+    #[error("Token {token_id} is not owned by the sender.")]
+    NotTokenOwner { token_id: String },
+
+    #[error("This address is not a member of the poll's group.")]
+    NotMember {},
+
+    #[error("This poll is weighted by cw20 tokens, send them with a vote.")]
+    TokenPoll {},
+
+    #[error("This poll does not take votes with this token.")]
+    NotTokenPoll {},
+
+    #[error("Token weighted polls can't take secret ballots.")]
+    TokenSecretBallot {},
+
+    #[error("Tokens can only be claimed once the poll has ended.")]
+    PollNotClosed {},
+
+    #[error("This address has no tokens escrowed on this poll.")]
+    NoEscrow {},
+
+    #[error("Hook is already registered.")]
+    HookAlreadyRegistered {},
+
+    #[error("Hook is not registered.")]
+    HookNotRegistered {},
+
+    #[error("Can't migrate from contract {contract}.")]
+    WrongContract { contract: String },
+
+    #[error("Invalid contract version {version}.")]
+    InvalidVersion { version: String },
+
+    #[error("Can't migrate from version {stored} down to {current}.")]
+    CannotDowngrade { stored: String, current: String },
+
     #[error("A strange problem occurred.")]
     WeirdProblem{},
 }
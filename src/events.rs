@@ -0,0 +1,142 @@
+use cosmwasm_std::{to_vec, Addr, Coin, Event, StdResult, Uint128};
+use cw_utils::Expiration;
+use serde::Serialize;
+
+use crate::state::{Ballot, Config, DelegationScope, Poll, PollResult, Role};
+
+// Events an indexer can rebuild the contract state from
+// They show up in transactions prefixed with wasm-, e.g. wasm-vote_cast.
+// Lists like ballot options and tallies are JSON encoded, tallies as [option, weight] pairs.
+
+fn json<T: Serialize>(value: &T) -> StdResult<String> {
+    Ok(String::from_utf8(to_vec(value)?)?)
+}
+
+pub fn poll_created(poll_id: u64, poll: &Poll) -> StdResult<Event> {
+    let options: Vec<&String> = poll.options.iter().map(|option| &option.0).collect();
+    let mut event = Event::new("poll_created")
+        .add_attribute("poll_id", poll_id.to_string())
+        .add_attribute("creator", &poll.creator)
+        .add_attribute("question", &poll.question)
+        .add_attribute("options", json(&options)?)
+        .add_attribute("poll_type", json(&poll.poll_type)?);
+    if let Some(external_ref) = &poll.external_ref {
+        event = event.add_attribute("external_ref", external_ref);
+    }
+    if let Some(category) = &poll.category {
+        event = event.add_attribute("category", category);
+    }
+    Ok(event)
+}
+
+// vote_cast for a first ballot, vote_changed with the previous ballot when it replaces one
+// Ballots cast with an NFT carry its token_id, the previous ballot being the token's
+// The tallies and turnout are the poll's after the ballot was counted
+pub fn vote(
+    poll_id: u64,
+    voter: &Addr,
+    token_id: Option<&str>,
+    previous: Option<&Ballot>,
+    ballot: &Ballot,
+    poll: &Poll,
+) -> StdResult<Event> {
+    let mut event = Event::new(if previous.is_some() { "vote_changed" } else { "vote_cast" })
+        .add_attribute("poll_id", poll_id.to_string())
+        .add_attribute("voter", voter);
+    if let Some(token_id) = token_id {
+        event = event.add_attribute("token_id", token_id);
+    }
+    if let Some(previous) = previous {
+        event = event
+            .add_attribute("previous_options", json(&previous.options)?)
+            .add_attribute("previous_weight", previous.weight.to_string());
+    }
+    Ok(event
+        .add_attribute("options", json(&ballot.options)?)
+        .add_attribute("weight", ballot.weight.to_string())
+        .add_attribute("tallies", json(&poll.options)?)
+        .add_attribute("turnout", poll.turnout.to_string()))
+}
+
+// A secret ballot committed, or a commitment replaced, with the weight it will count with
+pub fn vote_committed(poll_id: u64, voter: &Addr, weight: Uint128) -> Event {
+    Event::new("vote_committed")
+        .add_attribute("poll_id", poll_id.to_string())
+        .add_attribute("voter", voter)
+        .add_attribute("weight", weight.to_string())
+}
+
+pub fn delegated(delegator: &Addr, delegate: &Addr, scope: &DelegationScope) -> Event {
+    Event::new("delegated")
+        .add_attribute("delegator", delegator)
+        .add_attribute("delegate", delegate)
+        .add_attribute("scope", scope.key())
+}
+
+pub fn undelegated(delegator: &Addr, scope: &DelegationScope) -> Event {
+    Event::new("undelegated")
+        .add_attribute("delegator", delegator)
+        .add_attribute("scope", scope.key())
+}
+
+// cw20 tokens escrowed on a token weighted poll sent back to the voter
+pub fn tokens_claimed(poll_id: u64, voter: &Addr, token: &Addr, amount: Uint128) -> Event {
+    Event::new("tokens_claimed")
+        .add_attribute("poll_id", poll_id.to_string())
+        .add_attribute("voter", voter)
+        .add_attribute("token", token)
+        .add_attribute("amount", amount.to_string())
+}
+
+pub fn poll_closed(poll_id: u64, poll: &Poll, result: &PollResult) -> StdResult<Event> {
+    let mut event = Event::new("poll_closed")
+        .add_attribute("poll_id", poll_id.to_string())
+        .add_attribute("outcome", result.outcome.as_str());
+    if let Some(winner) = &result.winner {
+        event = event.add_attribute("winner", winner);
+    }
+    Ok(event
+        .add_attribute("tallies", json(&poll.options)?)
+        .add_attribute("turnout", poll.turnout.to_string()))
+}
+
+// The messages of a passed poll dispatched, with the funds they take from the treasury
+pub fn poll_executed(poll_id: u64, messages: usize, spent: &[Coin]) -> StdResult<Event> {
+    Ok(Event::new("poll_executed")
+        .add_attribute("poll_id", poll_id.to_string())
+        .add_attribute("messages", messages.to_string())
+        .add_attribute("spent", json(&spent)?))
+}
+
+// The fees and deposit settings after an update, as JSON
+pub fn config_updated(config: &Config) -> StdResult<Event> {
+    Ok(Event::new("config_updated")
+        .add_attribute("fees", json(&config.fees)?)
+        .add_attribute("deposit", json(&config.deposit)?))
+}
+
+pub fn fees_withdrawn(recipient: &Addr, amount: &Coin) -> Event {
+    Event::new("fees_withdrawn")
+        .add_attribute("recipient", recipient)
+        .add_attribute("amount", amount.to_string())
+}
+
+pub fn hook_changed(hook: &Addr, added: bool) -> Event {
+    Event::new("hook_changed")
+        .add_attribute("hook", hook)
+        .add_attribute("change", if added { "added" } else { "removed" })
+}
+
+pub fn ownership_proposed(pending_owner: &Addr, expires: &Expiration) -> Event {
+    Event::new("ownership_proposed")
+        .add_attribute("pending_owner", pending_owner)
+        .add_attribute("expires", expires.to_string())
+}
+
+// A role granted to or revoked from an address
+pub fn admin_changed(address: &Addr, role: Role, granted: bool) -> Event {
+    Event::new("admin_changed")
+        .add_attribute("address", address)
+        .add_attribute("role", role.as_str())
+        .add_attribute("change", if granted { "granted" } else { "revoked" })
+}
@@ -1 +1,156 @@
+use cosmwasm_std::{to_vec, Addr, Binary, StdResult, Uint128};
+use sha2::{Digest, Sha256};
 
+use crate::state::{Ballot, Outcome, Poll, PollResult, Runoff, RunoffRound};
+
+// Counts ranked ballots by instant-runoff
+// Each round a ballot counts its weight for its highest ranked option that is still in the running.
+// An option with more than half of the counted ballots wins, otherwise the option with the
+// fewest votes is eliminated. Ties for elimination knock out the option listed last on the poll.
+pub fn instant_runoff(options: &[String], ballots: &[Ballot]) -> Runoff {
+    let mut continuing: Vec<String> = options.to_vec();
+    let mut rounds: Vec<RunoffRound> = vec![];
+
+    loop {
+        let mut tallies: Vec<(String, Uint128)> = continuing
+            .iter()
+            .map(|option| (option.clone(), Uint128::zero()))
+            .collect();
+        for ballot in ballots {
+            if let Some(choice) = ballot.options.iter().find(|choice| continuing.contains(choice)) {
+                let position = tallies.iter().position(|tally| &tally.0 == choice).unwrap();
+                tallies[position].1 += ballot.weight;
+            }
+        }
+
+        let total: Uint128 = tallies.iter().map(|tally| tally.1).sum();
+        if total.is_zero() {
+            rounds.push(RunoffRound { tallies, eliminated: None });
+            return Runoff { rounds, winner: None };
+        }
+
+        // Leader is the first option with the highest tally
+        let leader = tallies
+            .iter()
+            .fold(&tallies[0], |best, tally| if tally.1 > best.1 { tally } else { best })
+            .clone();
+        if leader.1 + leader.1 > total || continuing.len() == 1 {
+            rounds.push(RunoffRound { tallies, eliminated: None });
+            return Runoff { rounds, winner: Some(leader.0) };
+        }
+
+        // Loser is the last option with the lowest tally
+        let loser = tallies
+            .iter()
+            .fold(&tallies[0], |worst, tally| if tally.1 <= worst.1 { tally } else { worst })
+            .0
+            .clone();
+        continuing.retain(|option| option != &loser);
+        rounds.push(RunoffRound { tallies, eliminated: Some(loser) });
+    }
+}
+
+// Credits spent by a quadratic ballot
+// Every entry is one vote, so an option listed n times costs n² credits
+pub fn quadratic_cost(votes: &[String]) -> u64 {
+    let mut counts: Vec<(&String, u64)> = vec![];
+    for vote in votes {
+        match counts.iter_mut().find(|count| count.0 == vote) {
+            Some(count) => count.1 += 1,
+            None => counts.push((vote, 1)),
+        }
+    }
+    counts.iter().map(|count| count.1 * count.1).sum()
+}
+
+// Works out the final result of a poll from its tallies, or from its runoff for ranked choice polls
+pub fn poll_result(poll: &Poll, runoff: Option<&Runoff>) -> PollResult {
+    if let Some(quorum) = &poll.quorum {
+        if poll.turnout.is_zero() || !quorum.is_reached(poll.turnout, poll.turnout) {
+            return PollResult { outcome: Outcome::NoQuorum, winner: None };
+        }
+    }
+    if let Some(veto) = &poll.veto {
+        let vetoes = poll.options.iter().find(|option| option.0 == veto.option);
+        if vetoes.map_or(false, |option| option.1 > poll.turnout * veto.threshold) {
+            return PollResult { outcome: Outcome::Vetoed, winner: None };
+        }
+    }
+
+    // The tallies the winner is picked from
+    let tallies = match runoff {
+        Some(runoff) => &runoff.rounds.last().unwrap().tallies,
+        None => &poll.options,
+    };
+    let votes_cast: Uint128 = tallies.iter().map(|tally| tally.1).sum();
+    let winner = match runoff {
+        Some(runoff) => runoff
+            .winner
+            .as_ref()
+            .and_then(|winner| tallies.iter().find(|tally| &tally.0 == winner)),
+        None => tallies
+            .iter()
+            .fold(None, |best: Option<&(String, Uint128)>, tally| match best {
+                Some(best) if best.1 >= tally.1 => Some(best),
+                _ if tally.1.is_zero() => best,
+                _ => Some(tally),
+            }),
+    };
+
+    match winner {
+        Some((option, weight)) => {
+            let passed = match &poll.threshold {
+                Some(threshold) => threshold.is_reached(*weight, votes_cast),
+                None => true,
+            };
+            PollResult {
+                outcome: if passed { Outcome::Passed } else { Outcome::Rejected },
+                winner: Some(option.clone()),
+            }
+        }
+        None => PollResult { outcome: Outcome::Rejected, winner: None },
+    }
+}
+
+// Prefixes keeping leaf hashes apart from node hashes, so a node can't pass for a leaf
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+// Hash of an allowlist leaf, the weight is separated from the address by a colon
+// Addresses never contain one, so no two leaves share an encoding
+pub fn allowlist_leaf(address: &Addr, weight: Option<Uint128>) -> Vec<u8> {
+    let leaf = match weight {
+        Some(weight) => format!("{}:{}", address, weight),
+        None => address.to_string(),
+    };
+    Sha256::new().chain(LEAF_PREFIX).chain(leaf.as_bytes()).finalize().to_vec()
+}
+
+// Hash of an allowlist node from its two children, smaller hash first
+pub fn allowlist_node(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    Sha256::new().chain(NODE_PREFIX).chain(first).chain(second).finalize().to_vec()
+}
+
+// Checks the address, with its weight if it has one, is a leaf of the Merkle tree with the root
+// Walks up the tree hashing the node with each sibling in the proof
+pub fn verify_allowlist(
+    root: &Binary,
+    address: &Addr,
+    weight: Option<Uint128>,
+    proof: &[Binary],
+) -> bool {
+    let mut hash = allowlist_leaf(address, weight);
+    for sibling in proof {
+        hash = allowlist_node(&hash, sibling);
+    }
+    hash == root.as_slice()
+}
+
+// Commitment for a secret ballot, the sha256 of [poll_id, voter, vote] as JSON followed by the salt
+// Binding the voter and poll keeps a copied commitment from revealing someone else's vote
+pub fn commitment(poll_id: u64, voter: &Addr, vote: &[String], salt: &str) -> StdResult<Binary> {
+    let mut preimage = to_vec(&(poll_id, voter, vote))?;
+    preimage.extend_from_slice(salt.as_bytes());
+    Ok(Binary::from(Sha256::digest(&preimage).to_vec()))
+}
@@ -0,0 +1,63 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{to_binary, Addr, CosmosMsg, StdResult, Storage, SubMsg, Uint128, WasmMsg};
+
+use crate::state::{Outcome, HOOKS};
+
+// Sent to every hook contract when a ballot is counted
+// previous is the ballot it replaced when the voter changed their vote
+// On NFT gated polls every token votes separately and token_id is the one voting
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VoteHookMsg {
+    pub poll_id: u64,
+    pub voter: String,
+    pub token_id: Option<String>,
+    pub previous: Option<Vec<String>>,
+    pub options: Vec<String>,
+    pub weight: Uint128,
+}
+
+// Sent to every hook contract when a poll is created or closed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PollHookMsg {
+    Created {
+        poll_id: u64,
+        creator: String,
+    },
+    Closed {
+        poll_id: u64,
+        outcome: Outcome,
+        winner: Option<String>,
+    },
+}
+
+// The execute message hook contracts have to accept, like cw4's MemberChangedHook
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HookExecuteMsg {
+    VoteHook(VoteHookMsg),
+    PollHook(PollHookMsg),
+}
+
+impl HookExecuteMsg {
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg: to_binary(&self)?,
+            funds: vec![],
+        }
+        .into())
+    }
+}
+
+// One sub-message per registered hook, a failing hook fails the whole transaction
+pub fn prepare_hooks(storage: &dyn Storage, msg: HookExecuteMsg) -> StdResult<Vec<SubMsg>> {
+    HOOKS
+        .may_load(storage)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|hook: Addr| msg.clone().into_cosmos_msg(hook).map(SubMsg::new))
+        .collect()
+}
@@ -1,6 +1,9 @@
 pub mod contract;
 mod error;
+mod events;
 pub mod helpers;
+pub mod hooks;
+mod migrations;
 pub mod msg;
 pub mod state;
 
@@ -0,0 +1,140 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Env, Order, StdResult, Storage, Uint128};
+use cw_storage_plus::{Item, Map};
+
+use crate::error::ContractError;
+use crate::helpers::poll_result;
+use crate::state::{
+    ballots, polls, Ballot, Config, Fees, Poll, PollType, Role, CONFIG, EXTERNAL_REFS, POLL_COUNT,
+    ROLES,
+};
+
+// A state migration, run when migrating from a version older than the one it is registered under
+pub type Migration = fn(&mut dyn Storage, &Env) -> Result<(), ContractError>;
+
+// Every state migration in the order they have to run
+pub const MIGRATIONS: &[(&str, Migration)] = &[("0.2.0", migrate_v0_2_0)];
+
+// State layout of 0.1.0, kept around so it can be migrated
+pub mod v0_1_0 {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct Ballot {
+        pub option: String,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct Poll {
+        pub creator: Addr,
+        pub question: String,
+        pub options: Vec<(String, u64)>,
+        pub closed: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct Config {
+        pub admins: Vec<Addr>,
+    }
+
+    pub const CONFIG: Item<Config> = Item::new("config");
+    pub const POLLS: Map<&str, Poll> = Map::new("polls");
+    pub const BALLOTS: Map<(Addr, &str), Ballot> = Map::new("ballots");
+}
+
+// 0.2.0 moved admins to roles, numbered polls and weighted ballots
+// The first admin becomes the owner and the others poll creators, like AddAdmin does now.
+// Polls are numbered in the order of their old ids, which are kept as their external reference.
+// Every old vote counted once so ballots get a weight of one.
+// 0.1.0 overwrote polls created with an id already in use, leaving ballots for options the poll
+// no longer has and counts that don't match its ballots. Those ballots are dropped and the tallies
+// are counted again from the ballots that are left.
+fn migrate_v0_2_0(storage: &mut dyn Storage, env: &Env) -> Result<(), ContractError> {
+    let legacy_config = v0_1_0::CONFIG.load(storage)?;
+    for (i, admin) in legacy_config.admins.iter().enumerate() {
+        let role = if i == 0 { Role::Owner } else { Role::PollCreator };
+        ROLES.save(storage, (admin, role), &())?;
+    }
+    let config = Config {
+        validators: None,
+        fees: Fees::default(),
+        deposit: None,
+        group_addr: None,
+    };
+    CONFIG.save(storage, &config)?;
+
+    // Old and new records share their namespaces, so the old ones are all cleared before saving
+    let legacy_polls = v0_1_0::POLLS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let legacy_ballots = v0_1_0::BALLOTS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (poll_id, _) in &legacy_polls {
+        v0_1_0::POLLS.remove(storage, poll_id);
+    }
+    for ((voter, poll_id), _) in &legacy_ballots {
+        v0_1_0::BALLOTS.remove(storage, (voter.clone(), poll_id));
+    }
+
+    let mut poll_count = POLL_COUNT.may_load(storage)?.unwrap_or_default();
+    for (external_ref, legacy) in legacy_polls {
+        poll_count += 1;
+        let mut options: Vec<(String, Uint128)> = legacy
+            .options
+            .into_iter()
+            .map(|(option, _)| (option, Uint128::zero()))
+            .collect();
+        for ((voter, poll_ref), legacy_ballot) in &legacy_ballots {
+            if poll_ref != &external_ref {
+                continue;
+            }
+            if let Some(tally) = options.iter_mut().find(|option| option.0 == legacy_ballot.option) {
+                tally.1 += Uint128::new(1);
+                let ballot = Ballot {
+                    poll_id: poll_count,
+                    options: vec![legacy_ballot.option.clone()],
+                    weight: Uint128::new(1),
+                };
+                ballots().save(storage, (voter.clone(), poll_count), &ballot)?;
+            }
+        }
+        let mut poll = Poll {
+            creator: legacy.creator,
+            external_ref: Some(external_ref.clone()),
+            created: env.block.time,
+            question: legacy.question,
+            turnout: options.iter().map(|option| option.1).sum(),
+            options,
+            closed: legacy.closed,
+            poll_type: PollType::Plurality,
+            category: None,
+            start: None,
+            end: None,
+            quorum: None,
+            threshold: None,
+            result: None,
+            msgs: vec![],
+            executed: false,
+            reveal_end: None,
+            unrevealed: Uint128::zero(),
+            deposit: None,
+            burn: false,
+            veto: None,
+            token: None,
+            group: None,
+            nft: None,
+            merkle_root: None,
+        };
+        // Closed polls never got a result before
+        if poll.closed {
+            poll.result = Some(poll_result(&poll, None));
+        }
+        polls().save(storage, poll_count, &poll)?;
+        EXTERNAL_REFS.save(storage, &external_ref, &poll_count)?;
+    }
+    POLL_COUNT.save(storage, &poll_count)?;
+    Ok(())
+}
@@ -1,55 +1,369 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use cosmwasm_std::{Addr, Binary, Coin, CosmosMsg, Timestamp, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw_utils::Expiration;
+
+use crate::state::{DelegationScope, DepositConfig, Fees, PollType, Role, Threshold};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct InstantiateMsg {
+    // Gets the owner role, defaults to the sender
     pub admin: Option<String>,
+    // Only stake delegated to these validators counts as voting power
+    pub validators: Option<Vec<String>>,
+    // Defaults to 1ukuji for every action
+    pub fees: Option<Fees>,
+    // Defaults to no deposit, with only admins creating polls
+    pub deposit: Option<DepositConfig>,
+    // cw4 group whose member weights replace delegated stake as voting power
+    pub group_addr: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
+    // The poll gets the next id, which is returned in the poll_id attribute and as data
     CreatePoll {
-        poll_id: String,
+        // Optional client side reference, rejected if another poll already has it
+        external_ref: Option<String>,
         question: String,
         options: Vec<String>,
+        #[serde(default)]
+        poll_type: PollType,
+        category: Option<String>,
+        // Height or time voting opens, None opens it straight away
+        start: Option<Expiration>,
+        // Height or time voting ends, None keeps it open until it is closed
+        end: Option<Expiration>,
+        // Makes ballots secret, they are committed until end and revealed until reveal_end
+        reveal_end: Option<Expiration>,
+        quorum: Option<Threshold>,
+        threshold: Option<Threshold>,
+        // Makes the poll weighted by this cw20 token, voters vote by sending it with ReceiveMsg::Vote
+        token: Option<String>,
+        // Gates the poll to holders of this cw721 collection, every token votes once with NftVote
+        nft: Option<String>,
+        // Restricts voting to the addresses in a Merkle tree, voters prove they are in it when voting
+        merkle_root: Option<Binary>,
+        // Messages to run if the poll passes
+        // Only for plurality polls with a yes and a no option, in that order
+        #[serde(default)]
+        msgs: Vec<CosmosMsg>,
     },
+    // A plurality vote holds a single option, a ranked choice vote lists options by preference
+    // and an approval vote lists every approved option. A quadratic vote lists an option once per vote
+    // Votes on allowlisted polls need a proof the sender is in the poll's Merkle tree
     Vote {
-        poll_id: String,
-        vote: String,
+        poll_id: u64,
+        vote: Vec<String>,
+        proof: Option<AllowlistProof>,
+    },
+    // Votes on an NFT gated poll with every listed token the sender owns, each token is one vote
+    // A token that already voted has its ballot replaced, whoever cast it
+    NftVote {
+        poll_id: u64,
+        token_ids: Vec<String>,
+        vote: Vec<String>,
+    },
+    // Lets `to` vote with the sender's power on polls in scope, unless the sender votes themselves
+    // Secret ballot polls only count the voter's own power
+    Delegate {
+        to: String,
+        scope: DelegationScope,
+    },
+    Undelegate { scope: DelegationScope },
+    // Commits to a secret ballot, commitment is the sha256 of [poll_id, voter, vote] as JSON
+    // followed by the salt, so nobody else can copy it and reveal the same vote
+    Commit {
+        poll_id: u64,
+        commitment: Binary,
+    },
+    Reveal {
+        poll_id: u64,
+        vote: Vec<String>,
+        salt: String,
     },
-    ClosePoll {poll_id: String},
+    ClosePoll {poll_id: u64},
+    // Dispatches the messages of a poll that passed
+    Execute { poll_id: u64 },
+    // Grants the poll_creator role
     AddAdmin {addmin: String}, 
+    // Revokes every role the address holds apart from owner
+    RemoveAdmin { address: String },
+    GrantRole { address: String, role: Role },
+    RevokeRole { address: String, role: Role },
+    // Offers ownership to new_owner until expiry, which defaults to a day
+    ProposeOwner {
+        new_owner: String,
+        expiry: Option<Expiration>,
+    },
+    AcceptOwnership {},
+    // Leaves the contract without an owner for good
+    RenounceOwnership {},
+    UpdateConfig {
+        fees: Option<Fees>,
+        deposit: Option<DepositConfig>,
+    },
+    // Sends collected fees out of the treasury
+    WithdrawFees { amount: Coin, recipient: String },
+    // Votes on token weighted polls, sent by the token contract
+    Receive(Cw20ReceiveMsg),
+    // Sends back the tokens escrowed on a poll that has ended or been closed
+    ClaimTokens { poll_id: u64 },
+    // Registers a contract to receive a VoteHookMsg or PollHookMsg on every vote and poll change
+    AddHook { addr: String },
+    RemoveHook { addr: String },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    AllPolls {},
-    Poll { poll_id: String },
-    Vote { poll_id: String, address: String },
+    // Polls ordered by id, limit defaults to 10 and is capped at 30
+    // Every filter given has to match
+    // A page looks at no more than 100 polls, next_start_after continues where it stopped
+    AllPolls {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        closed: Option<bool>,
+        creator: Option<String>,
+        created_after: Option<Timestamp>,
+        created_before: Option<Timestamp>,
+    },
+    Poll { poll_id: u64 },
+    // Looks up the id of the poll created with the reference
+    ExternalRef { external_ref: String },
+    Vote { poll_id: u64, address: String },
+    // The ballot an NFT cast on an NFT gated poll
+    NftVote { poll_id: u64, token_id: String },
+    // Every ballot on the poll ordered by voter address, limit defaults to 10 and is capped at 30
+    // Ballots cast by NFTs are listed by NftBallots instead
+    Ballots {
+        poll_id: u64,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Every ballot the NFTs cast on an NFT gated poll ordered by token id
+    // limit defaults to 10 and is capped at 30
+    NftBallots {
+        poll_id: u64,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Every poll the address voted on ordered by poll id, limit defaults to 10 and is capped at 30
+    // NFT ballots are keyed on the token rather than the voter, so they aren't listed here
+    VoterBallots {
+        address: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    Runoff { poll_id: u64 },
+    // Credits the address has left, NFT gated polls give each token its own credits
+    // so they look up the ballot of token_id instead
+    Credits {
+        poll_id: u64,
+        address: String,
+        token_id: Option<String>,
+    },
+    Config {},
+    Treasury {},
+    Hooks {},
+    // cw20 tokens the address has escrowed on the poll
+    Escrow { poll_id: u64, address: String },
+    // Every address holding the role
+    RoleHolders { role: Role },
+    // Every address holding a role, with its roles
+    Admins {},
+    Ownership {},
+    // Who the address's power flows to on polls of the category, in order
+    DelegationChain {
+        address: String,
+        category: Option<String>,
+    },
+}
+
+// Proof that an address, with an optional weight, is a leaf of a poll's Merkle tree
+// Trees built off-chain have to hash the same way:
+// - a leaf is sha256(0x00 ++ address), or sha256(0x00 ++ address ++ ":" ++ weight) with a weight
+//   written as a decimal string, e.g. "kujira1...:25"
+// - a node is sha256(0x01 ++ left ++ right), the smaller of the two child hashes going left
+// Each proof entry is the sibling hash on the way up from the leaf.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowlistProof {
+    pub proof: Vec<Binary>,
+    // Ballot weight, ballots without one weigh 1
+    pub weight: Option<Uint128>,
+}
+
+// The part of the cw4 group query interface used to look up voters
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw4QueryMsg {
+    Member { addr: String, at_height: Option<u64> },
+    TotalWeight { at_height: Option<u64> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MemberResponse {
+    pub weight: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TotalWeightResponse {
+    pub weight: u64,
+}
+
+// The part of the cw721 query interface used to check who owns a token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw721QueryMsg {
+    OwnerOf {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnerOfResponse {
+    pub owner: String,
+    pub approvals: Vec<Approval>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Approval {
+    pub spender: String,
+    pub expires: Expiration,
+}
+
+// Payload of a cw20 Send to this contract, the amount sent is escrowed and added to the ballot weight
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    Vote { poll_id: u64, vote: Vec<String> },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum MigrateMsg {}
+pub struct MigrateMsg {}
 
 // Needed import
-use crate::state::{Ballot, Poll};
+use crate::state::{Ballot, Config, NftBallot, PendingOwner, Poll, PollStatus, Runoff};
 
 // Needed macro derivations
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct AllPollsResponse {
-    pub polls: Vec<Poll>,
+    pub polls: Vec<PollInfo>,
+    // Where the next page starts, None once every poll has been looked at
+    pub next_start_after: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PollInfo {
+    pub poll_id: u64,
+    pub poll: Poll,
+    pub status: PollStatus,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct PollResponse {
     pub poll: Option<Poll>,
+    pub status: Option<PollStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ExternalRefResponse {
+    pub poll_id: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct VoteResponse {
     pub vote: Option<Ballot>,
 }
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RunoffResponse {
+    pub runoff: Option<Runoff>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigResponse {
+    pub config: Config,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TreasuryResponse {
+    pub balances: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct NftVoteResponse {
+    pub vote: Option<NftBallot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct EscrowResponse {
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct HooksResponse {
+    pub hooks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CreditsResponse {
+    // Credits the address has left to spend, None if the poll is not a quadratic poll
+    pub remaining: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RoleHoldersResponse {
+    pub holders: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct BallotsResponse {
+    pub ballots: Vec<(Addr, Ballot)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct NftBallotsResponse {
+    // Token id and the ballot it cast
+    pub ballots: Vec<(String, NftBallot)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VoterBallotsResponse {
+    pub ballots: Vec<VoterBallot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VoterBallot {
+    pub poll_id: u64,
+    pub ballot: Ballot,
+    pub status: PollStatus,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AdminsResponse {
+    pub admins: Vec<AdminInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AdminInfo {
+    pub address: Addr,
+    pub roles: Vec<Role>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct OwnershipResponse {
+    pub owner: Option<Addr>,
+    pub pending_owner: Option<PendingOwner>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DelegationChainResponse {
+    pub chain: Vec<Addr>,
+}
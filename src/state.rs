@@ -3,39 +3,469 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Addr;
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{
+    Addr, Binary, BlockInfo, Coin, CosmosMsg, Decimal, StdError, StdResult, Timestamp, Uint128,
+};
+use cw_storage_plus::{
+    Index, IndexList, IndexedMap, Item, Key, KeyDeserialize, Map, MultiIndex, PrimaryKey,
+};
+use cw_utils::Expiration;
 
 // Derive JSON serialisation
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-// Public ballot struct that stores option casted
+// Public ballot struct that stores the options casted
+// For ranked choice polls the options are ordered from most to least preferred
+// For approval polls they are every option the voter approves of
+// For quadratic polls every entry is one vote, an option listed n times gets n votes
+// The weight is the voting power the ballot was tallied with, so it can be taken back on a revote
 pub struct Ballot {
-    pub option: String,
+    // Kept on the ballot so ballots can be indexed by poll
+    pub poll_id: u64,
+    pub options: Vec<String>,
+    pub weight: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+// How ballots on a poll are cast and counted
+pub enum PollType {
+    // One option per ballot, the option with the most votes wins
+    Plurality,
+    // Ballots rank the options, the winner is found by instant-runoff when the poll is closed
+    RankedChoice,
+    // Ballots approve up to max_selections options, each approved option gets one vote
+    Approval { max_selections: u32 },
+    // Each voter spends a budget of credits, n votes on one option cost n² credits
+    Quadratic { credits: u64 },
 }
+
+// Polls created without a type are plurality polls
+impl Default for PollType {
+    fn default() -> Self {
+        PollType::Plurality
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-// Public poll struct that stores creator address, a question, and a vector of options and their corresponding votes
+// Public poll struct that stores creator address, a question, and a vector of options and their corresponding weight of votes
+// For ranked choice polls the votes are first preferences
 pub struct Poll {
     pub creator: Addr,
+    // Reference the client created the poll with, unique across polls
+    pub external_ref: Option<String>,
+    // Block time the poll was created at
+    pub created: Timestamp,
     pub question: String,
-    pub options: Vec<(String, u64)>,
+    pub options: Vec<(String, Uint128)>,
     pub closed: bool,
+    pub poll_type: PollType,
+    // Voters can delegate to someone for just the polls of one category
+    pub category: Option<String>,
+    // Votes are accepted once start is reached and until end is reached
+    pub start: Option<Expiration>,
+    pub end: Option<Expiration>,
+    // Sum of the weights of every ballot cast
+    pub turnout: Uint128,
+    // Turnout needed for the poll to count, None always counts
+    pub quorum: Option<Threshold>,
+    // Weight the winning option needs to pass, None passes with any winner
+    pub threshold: Option<Threshold>,
+    // Set by close_poll
+    pub result: Option<PollResult>,
+    // Messages dispatched by ExecuteMsg::Execute once the poll passes with its first (yes) option
+    pub msgs: Vec<CosmosMsg>,
+    pub executed: bool,
+    // Secret ballots are committed until end and revealed until reveal_end
+    pub reveal_end: Option<Expiration>,
+    // Weight committed that hasn't been revealed, it is never tallied
+    pub unrevealed: Uint128,
+    // Locked by the creator, refunded when the poll closes unless it misses quorum or is vetoed
+    pub deposit: Option<Coin>,
+    // Burn a lost deposit instead of adding it to the treasury, as configured at creation
+    pub burn: bool,
+    pub veto: Option<Veto>,
+    // cw20 token the poll is voted on with, ballots weigh the tokens escrowed with them
+    pub token: Option<Addr>,
+    // cw4 group voters are weighted by, as it was when the poll was created
+    pub group: Option<GroupSnapshot>,
+    // cw721 collection whose tokens vote on the poll instead of addresses
+    pub nft: Option<Addr>,
+    // Root of the Merkle tree of addresses allowed to vote
+    pub merkle_root: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// A cw4 group and the height its member weights are read at
+pub struct GroupSnapshot {
+    pub addr: Addr,
+    pub height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+// An amount of voting weight a poll has to reach, modelled on cw3's Threshold
+pub enum Threshold {
+    // A fixed amount of weight
+    AbsoluteCount { weight: Uint128 },
+    // A share of the weight of all votes cast on the poll
+    VotesCast { percentage: Decimal },
+    // A share of the total weight eligible to vote, given when the poll is created
+    // Group polls replace eligible with the group's total weight at the snapshot height
+    EligiblePower { percentage: Decimal, eligible: Uint128 },
+}
+
+impl Threshold {
+    // Whether weight reaches the threshold, votes_cast being the weight of all votes on the poll
+    pub fn is_reached(&self, weight: Uint128, votes_cast: Uint128) -> bool {
+        match self {
+            Threshold::AbsoluteCount { weight: required } => weight >= *required,
+            Threshold::VotesCast { percentage } => weight >= votes_cast * *percentage,
+            Threshold::EligiblePower { percentage, eligible } => weight >= *eligible * *percentage,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Passed,
+    Rejected,
+    NoQuorum,
+    Vetoed,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Passed => "passed",
+            Outcome::Rejected => "rejected",
+            Outcome::NoQuorum => "no_quorum",
+            Outcome::Vetoed => "vetoed",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// Final result of a closed poll
+// The winner is the option with the most weight, ties going to the option listed first,
+// or the instant-runoff winner for ranked choice polls
+pub struct PollResult {
+    pub outcome: Outcome,
+    pub winner: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+// Where a poll is in its lifetime, derived from the current block
+pub enum PollStatus {
+    Pending,
+    Open,
+    // Voting has ended and secret ballots can be revealed
+    Revealing,
+    Ended,
+    Closed,
+}
+
+impl Poll {
+    pub fn status(&self, block: &BlockInfo) -> PollStatus {
+        if self.closed {
+            PollStatus::Closed
+        } else if self.end.map_or(false, |end| end.is_expired(block)) {
+            if self.reveal_end.map_or(false, |reveal_end| !reveal_end.is_expired(block)) {
+                PollStatus::Revealing
+            } else {
+                PollStatus::Ended
+            }
+        } else if self.start.map_or(false, |start| !start.is_expired(block)) {
+            PollStatus::Pending
+        } else {
+            PollStatus::Open
+        }
+    }
+
+    // Tallies of secret ballot polls stay hidden until the reveal phase ends
+    pub fn tallies_hidden(&self, block: &BlockInfo) -> bool {
+        !self.closed && self.reveal_end.map_or(false, |reveal_end| !reveal_end.is_expired(block))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// A single counting round of an instant-runoff, with the option knocked out at the end of it
+pub struct RunoffRound {
+    pub tallies: Vec<(String, Uint128)>,
+    pub eliminated: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// Outcome of an instant-runoff, computed when a ranked choice poll is closed
+pub struct Runoff {
+    pub rounds: Vec<RunoffRound>,
+    pub winner: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
-    pub admins: Vec<Addr>,
+    // Validators whose delegations count as voting power, None counts every validator
+    pub validators: Option<Vec<String>>,
+    pub fees: Fees,
+    pub deposit: Option<DepositConfig>,
+    // cw4 group polls are weighted by, None weighs voters by delegated stake
+    pub group_addr: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// Deposit locked in the fee denom when creating a poll, on top of the fee
+pub struct DepositConfig {
+    // A zero amount turns deposits off
+    pub amount: Uint128,
+    // Lets addresses without the poll_creator role create polls by locking the deposit
+    pub open_creation: bool,
+    // Burn lost deposits instead of adding them to the treasury
+    pub burn: bool,
+    pub veto: Option<Veto>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// A poll is vetoed when more than threshold of its turnout votes for the veto option
+pub struct Veto {
+    pub option: String,
+    pub threshold: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// Fee charged in denom for each kind of action, a zero amount makes the action free
+pub struct Fees {
+    pub denom: String,
+    pub create_poll: Uint128,
+    pub vote: Uint128,
+    // Also charged for executing a passed poll
+    pub close_poll: Uint128,
+    // Granting roles and updating the config
+    pub admin: Uint128,
+}
+
+// Contracts instantiated without fees charge 1ukuji for everything
+impl Default for Fees {
+    fn default() -> Self {
+        Fees {
+            denom: "ukuji".to_string(),
+            create_poll: Uint128::new(1),
+            vote: Uint128::new(1),
+            close_poll: Uint128::new(1),
+            admin: Uint128::new(1),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+// Permissions an address can hold, the owner can do everything
+// Moderators grant and revoke the poll_creator and poll_closer roles
+// There is at most one owner, ownership only moves by a transfer the new owner accepts
+pub enum Role {
+    Owner,
+    PollCreator,
+    PollCloser,
+    FeeManager,
+    Moderator,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::PollCreator => "poll_creator",
+            Role::PollCloser => "poll_closer",
+            Role::FeeManager => "fee_manager",
+            Role::Moderator => "moderator",
+        }
+    }
+}
+
+// Lets roles be used in storage keys, stored by their snake_case name
+impl<'a> PrimaryKey<'a> for Role {
+    type Prefix = ();
+    type SubPrefix = ();
+    type Suffix = Self;
+    type SuperSuffix = Self;
+
+    fn key(&self) -> Vec<Key<'_>> {
+        vec![Key::Ref(self.as_str().as_bytes())]
+    }
 }
 
+impl KeyDeserialize for Role {
+    type Output = Role;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Role> {
+        match value.as_slice() {
+            b"owner" => Ok(Role::Owner),
+            b"poll_creator" => Ok(Role::PollCreator),
+            b"poll_closer" => Ok(Role::PollCloser),
+            b"fee_manager" => Ok(Role::FeeManager),
+            b"moderator" => Ok(Role::Moderator),
+            _ => Err(StdError::parse_err("Role", "unknown role")),
+        }
+    }
+}
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
-// A map with a String key and Poll value.
-// The key will be a UUID generated clientside
-pub const POLLS: Map<&str, Poll> = Map::new("polls");
+// A map with a composite key of (Address) and (Role), an entry means the address holds that role
+pub const ROLES: Map<(&Addr, Role), ()> = Map::new("roles");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// An owner proposed by the current owner, who has until expires to accept
+pub struct PendingOwner {
+    pub address: Addr,
+    pub expires: Expiration,
+}
+
+pub const PENDING_OWNER: Item<PendingOwner> = Item::new("pending_owner");
+
+// A map with a denom key and the amount of fees collected in that denom not yet withdrawn
+pub const TREASURY: Map<&str, Uint128> = Map::new("treasury");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// Ballot cast by a token of an NFT gated poll, along with the owner who cast it
+pub struct NftBallot {
+    pub voter: Addr,
+    pub ballot: Ballot,
+}
+
+// A map with a composite key of (Poll_ID) and (Token_ID), and the ballot the token cast
+// Keyed on the token so a transferred NFT replaces its ballot instead of voting twice
+pub const NFT_BALLOTS: Map<(u64, &str), NftBallot> = Map::new("nft_ballots");
+
+// A map with a composite key of (Voter Address) and (Poll_ID), and the cw20 tokens they escrowed
+// Voters claim them back once the poll is closed
+pub const ESCROWS: Map<(&Addr, u64), Uint128> = Map::new("escrows");
+
+// cw20 contracts token weighted polls escrow tokens in, poll messages can't be sent to them
+pub const ESCROW_TOKENS: Map<&Addr, ()> = Map::new("escrow_tokens");
+
+// Contracts sent a sub-message whenever a vote is counted or a poll is created or closed
+pub const HOOKS: Item<Vec<Addr>> = Item::new("hooks");
+
+pub struct PollIndexes<'a> {
+    pub creator: MultiIndex<'a, Addr, Poll, u64>,
+    // 1 for closed polls, 0 for the rest
+    pub closed: MultiIndex<'a, u8, Poll, u64>,
+}
+
+impl<'a> IndexList<Poll> for PollIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Poll>> + '_> {
+        let v: Vec<&dyn Index<Poll>> = vec![&self.creator, &self.closed];
+        Box::new(v.into_iter())
+    }
+}
+
+// Id of the last poll created, polls are numbered from 1
+pub const POLL_COUNT: Item<u64> = Item::new("poll_count");
+
+// A map with the client side reference of a poll as key and its Poll_ID
+pub const EXTERNAL_REFS: Map<&str, u64> = Map::new("external_refs");
+
+// A map with the Poll_ID as key and Poll value, indexed by creator and by whether it is closed
+pub fn polls<'a>() -> IndexedMap<'a, u64, Poll, PollIndexes<'a>> {
+    let indexes = PollIndexes {
+        creator: MultiIndex::new(|poll| poll.creator.clone(), "polls", "polls__creator"),
+        closed: MultiIndex::new(|poll| poll.closed as u8, "polls", "polls__closed"),
+    };
+    IndexedMap::new("polls", indexes)
+}
+
+pub struct BallotIndexes<'a> {
+    pub poll: MultiIndex<'a, u64, Ballot, (Addr, u64)>,
+}
+
+impl<'a> IndexList<Ballot> for BallotIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Ballot>> + '_> {
+        let v: Vec<&dyn Index<Ballot>> = vec![&self.poll];
+        Box::new(v.into_iter())
+    }
+}
 
 // A map with a composite key composed of (Voter Address) and (Poll_ID), and a Ballot instance
+// Indexed by poll so every ballot on a poll can be listed
 // Each ballot will be inserted via execute_vote
-pub const BALLOTS: Map<(Addr, &str), Ballot> = Map::new("ballots");
+pub fn ballots<'a>() -> IndexedMap<'a, (Addr, u64), Ballot, BallotIndexes<'a>> {
+    let indexes = BallotIndexes {
+        poll: MultiIndex::new(|ballot| ballot.poll_id, "ballots", "ballots__poll"),
+    };
+    IndexedMap::new("ballots", indexes)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+// Polls a delegation covers, a category delegation takes precedence over a global one
+pub enum DelegationScope {
+    Global,
+    Category(String),
+}
+
+impl DelegationScope {
+    pub fn key(&self) -> String {
+        match self {
+            DelegationScope::Global => "global".to_string(),
+            DelegationScope::Category(category) => format!("category:{}", category),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Delegation {
+    pub delegate: Addr,
+    pub scope: DelegationScope,
+}
+
+pub struct DelegationIndexes<'a> {
+    pub delegate: MultiIndex<'a, Addr, Delegation, (Addr, String)>,
+}
+
+impl<'a> IndexList<Delegation> for DelegationIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Delegation>> + '_> {
+        let v: Vec<&dyn Index<Delegation>> = vec![&self.delegate];
+        Box::new(v.into_iter())
+    }
+}
+
+// A map with a composite key of (Delegator Address) and (Scope key), indexed by delegate
+// so a delegate's delegators can be found when they vote
+pub fn delegations<'a>() -> IndexedMap<'a, (&'a Addr, &'a str), Delegation, DelegationIndexes<'a>> {
+    let indexes = DelegationIndexes {
+        delegate: MultiIndex::new(
+            |delegation| delegation.delegate.clone(),
+            "delegations",
+            "delegations__delegate",
+        ),
+    };
+    IndexedMap::new("delegations", indexes)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// Power of a delegator counted on someone else's ballot, taken back if the delegator votes
+pub struct ProxyVote {
+    pub voter: Addr,
+    pub weight: Uint128,
+}
+
+// A map with a composite key of (Poll_ID) and (Delegator Address), and the ballot their power went to
+pub const PROXY_VOTES: Map<(u64, &Addr), ProxyVote> = Map::new("proxy_votes");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+// A secret ballot, the sha256 of [poll_id, voter, vote] as JSON followed by the salt
+// The weight is the voting power when it was committed
+pub struct Commit {
+    pub commitment: Binary,
+    pub weight: Uint128,
+}
+
+// A map with a composite key of (Voter Address) and (Poll_ID), and the commitment not yet revealed
+pub const COMMITS: Map<(Addr, u64), Commit> = Map::new("commits");
+
+// A map with the Poll_ID as key and the instant-runoff of a closed ranked choice poll
+pub const RUNOFFS: Map<u64, Runoff> = Map::new("runoffs");
 
 
 